@@ -1,18 +1,75 @@
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
-use std::thread::JoinHandle;
 
-use display::Display;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+
+use code_write_monitor::CodeWriteMonitor;
+use display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use hud::Hud;
 use input::Input;
+use linter::{Hazard, Linter};
+use movie::Movie;
+use random::Random;
+use rewind::RewindBuffer;
 use sound::Sound;
+use sprite_viewer::SpriteViewer;
+use terminal::Terminal;
+use video::VideoRecorder;
+use window::Window;
+
+pub use code_write_monitor::CodeWriteAction;
+pub use control_flow::ControlFlowGraph;
+pub use controller::{ControllerInput, ControllerMapping};
+pub use crt_filter::{CrtFilter, FILTER_NAMES};
+pub use input_script::{read_input_script, InputTime, ScheduledInput};
+pub use keymap::{HostKey, Keymap, KEYMAP_NAMES};
+pub use movie::MovieError;
+pub use palette::{parse_color, Palette, THEME_NAMES};
+pub use persistence::Persistence;
+pub use save_state::{MachineState, SaveStateError};
+pub use screenshot::save_screenshot;
+pub use sprite_viewer::SpriteViewerConfig;
+pub use state_diff::{diff_states, show_state};
+pub use status::Status;
+pub use terminal::{check_terminal, TerminalConfig, DEFAULT_KEY_RELEASE_TIMEOUT};
+pub use window::DEFAULT_SCALE_FACTOR;
 
+mod code_write_monitor;
+mod control_flow;
+mod controller;
+mod crt_filter;
 mod display;
+mod hud;
+mod image;
 mod input;
+mod input_script;
+mod keymap;
+mod keypad_panel;
+mod linter;
+mod movie;
+mod palette;
+mod persistence;
+mod random;
+mod rewind;
+mod save_state;
+mod screenshot;
 mod sound;
+mod sprite_viewer;
+mod state_diff;
+mod status;
+mod terminal;
+mod user_data;
+mod video;
+mod window;
 
-const MEMORY_SIZE: u16 = 4096;
+pub const MEMORY_SIZE: u16 = 4096;
 const PROGRAM_OFFSET: u16 = 0x200;
 const FONT_STARTING_MEMORY: u16 = 0x050;
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+const FRAMES_PER_SECOND: u64 = 60;
 
 const FONTS: [u8; 16 * 5] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -34,7 +91,7 @@ const FONTS: [u8; 16 * 5] = [
 ];
 
 type Intermediate = u8;
-type Address = u16;
+pub type Address = u16;
 type RegisterIdentifier = u8;
 type Register = u8;
 
@@ -42,7 +99,7 @@ type AtomicRegister = AtomicU8;
 
 // this follows the wikipedia article to chip8,
 // meaning not the original CHIP8 instruction-set
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Instructions {
     ClearScreen,
     DrawSprite(RegisterIdentifier, RegisterIdentifier, Intermediate),
@@ -78,9 +135,242 @@ enum Instructions {
     StoreVxAsBCDInI(RegisterIdentifier),
     DumpRegisters(RegisterIdentifier),
     LoadRegisters(RegisterIdentifier),
+    StoreRegistersInRplFlags(RegisterIdentifier),
+    LoadRegistersFromRplFlags(RegisterIdentifier),
+    CallMachineCode(Address),
     Unkown,
 }
 
+fn decode(instruction: u16) -> Instructions {
+    if instruction == 0x00e0 {
+        return Instructions::ClearScreen;
+    } else if instruction & 0xf000 == 0x1000 {
+        return Instructions::UnconditionalJump(instruction & 0x0fff);
+    } else if instruction & 0xf000 == 0x6000 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        let intermediate = instruction as u8;
+        return Instructions::SetVxToIntermediate(register_identifier, intermediate);
+    } else if instruction & 0xf000 == 0x7000 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        let intermediate = instruction as u8;
+        return Instructions::AddIntermediateToVx(register_identifier, intermediate);
+    } else if instruction & 0xf000 == 0xa000 {
+        let address = instruction & 0x0fff;
+        return Instructions::SetIndexRegisterToIntermediate(address);
+    } else if instruction & 0xf000 == 0xd000 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        let height = (instruction & 0x000f) as u8;
+        return Instructions::DrawSprite(register_identifier_x, register_identifier_y, height);
+    } else if instruction & 0xf0ff == 0xe09e {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SkipIfKeyPressedVx(register_identifier);
+    } else if instruction & 0xf0ff == 0xe0a1 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SkipIfKeyNotPressedVx(register_identifier);
+    } else if instruction & 0xf0ff == 0xf00a {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::AwaitKeyPressVx(register_identifier);
+    } else if instruction == 0x00ee {
+        return Instructions::ReturnFromSubroutine;
+    } else if instruction & 0xf000 == 0x2000 {
+        let address = instruction & 0x0fff;
+        return Instructions::CallSubroutine(address);
+    } else if instruction & 0xf000 == 0x3000 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        let intermediate = instruction as u8;
+        return Instructions::SkipIfVxIsIntermediate(register_identifier, intermediate);
+    } else if instruction & 0xf000 == 0x4000 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        let intermediate = instruction as u8;
+        return Instructions::SkipIfVxIsNotIntermediate(register_identifier, intermediate);
+    } else if instruction & 0xf00f == 0x5000 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::SkipIfVxIsVy(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8000 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::SetVxToVy(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8001 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::BitwiseOrVyToVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8002 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::BitwiseAndVyToVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8003 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::BitwiseXorVyToVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8004 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::AddVyToVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8005 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::SubtractVyFromVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x8006 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::StoreLSBfromVxInVf(register_identifier);
+    } else if instruction & 0xf00f == 0x800E {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::StoreMSBfromVxInVf(register_identifier);
+    } else if instruction & 0xf00f == 0x8007 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::SetVxToVyMinusVx(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf00f == 0x9000 {
+        let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
+        let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
+        return Instructions::SkipIfVxIsNotVy(register_identifier_x, register_identifier_y);
+    } else if instruction & 0xf000 == 0xb000 {
+        let address = instruction & 0x0fff;
+        return Instructions::UnconditionalJumpWithOffset(address);
+    } else if instruction & 0xf000 == 0xc000 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        let intermediate = instruction as u8;
+        return Instructions::GenerateRandomNumberWithCap(register_identifier, intermediate);
+    } else if instruction & 0xf0ff == 0xf007 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SetVxToDelayTimer(register_identifier);
+    } else if instruction & 0xf0ff == 0xf018 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SetSoundTimerToVx(register_identifier);
+    } else if instruction & 0xf0ff == 0xf01e {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::AddVxToI(register_identifier);
+    } else if instruction & 0xf0ff == 0xf029 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SetIToSpriteLocation(register_identifier);
+    } else if instruction & 0xf0ff == 0xf033 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::StoreVxAsBCDInI(register_identifier);
+    } else if instruction & 0xf0ff == 0xf055 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::DumpRegisters(register_identifier);
+    } else if instruction & 0xf0ff == 0xf065 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::LoadRegisters(register_identifier);
+    } else if instruction & 0xf0ff == 0xf015 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::SetDelayTimerToVx(register_identifier);
+    } else if instruction & 0xf0ff == 0xf075 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::StoreRegistersInRplFlags(register_identifier);
+    } else if instruction & 0xf0ff == 0xf085 {
+        let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
+        return Instructions::LoadRegistersFromRplFlags(register_identifier);
+    } else if instruction & 0xf000 == 0x0000 && instruction != 0x0000 {
+        // 0000 stays unknown, it is what a program counter running off into zeroed
+        // memory finds
+        let address = instruction & 0x0fff;
+        return Instructions::CallMachineCode(address);
+    }
+
+    Instructions::Unkown
+}
+
+// disassembles instructions using the common (Cowgod's) CHIP-8 mnemonics
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instructions::ClearScreen => write!(f, "CLS"),
+            Instructions::DrawSprite(x, y, height) => {
+                write!(f, "DRW V{:X}, V{:X}, {}", x, y, height)
+            }
+            Instructions::UnconditionalJump(address) => write!(f, "JP {:#05x}", address),
+            Instructions::UnconditionalJumpWithOffset(address) => {
+                write!(f, "JP V0, {:#05x}", address)
+            }
+            Instructions::SetVxToIntermediate(x, value) => write!(f, "LD V{:X}, {:#04x}", x, value),
+            Instructions::AddIntermediateToVx(x, value) => {
+                write!(f, "ADD V{:X}, {:#04x}", x, value)
+            }
+            Instructions::SetIndexRegisterToIntermediate(address) => {
+                write!(f, "LD I, {:#05x}", address)
+            }
+            Instructions::SkipIfKeyPressedVx(x) => write!(f, "SKP V{:X}", x),
+            Instructions::SkipIfKeyNotPressedVx(x) => write!(f, "SKNP V{:X}", x),
+            Instructions::AwaitKeyPressVx(x) => write!(f, "LD V{:X}, K", x),
+            Instructions::ReturnFromSubroutine => write!(f, "RET"),
+            Instructions::CallSubroutine(address) => write!(f, "CALL {:#05x}", address),
+            Instructions::SkipIfVxIsIntermediate(x, value) => {
+                write!(f, "SE V{:X}, {:#04x}", x, value)
+            }
+            Instructions::SkipIfVxIsNotIntermediate(x, value) => {
+                write!(f, "SNE V{:X}, {:#04x}", x, value)
+            }
+            Instructions::SkipIfVxIsNotVy(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instructions::SkipIfVxIsVy(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instructions::SetVxToVy(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instructions::BitwiseOrVyToVx(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instructions::BitwiseAndVyToVx(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instructions::BitwiseXorVyToVx(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instructions::AddVyToVx(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instructions::SubtractVyFromVx(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instructions::StoreLSBfromVxInVf(x) => write!(f, "SHR V{:X}", x),
+            Instructions::StoreMSBfromVxInVf(x) => write!(f, "SHL V{:X}", x),
+            Instructions::SetVxToVyMinusVx(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instructions::GenerateRandomNumberWithCap(x, value) => {
+                write!(f, "RND V{:X}, {:#04x}", x, value)
+            }
+            Instructions::SetVxToDelayTimer(x) => write!(f, "LD V{:X}, DT", x),
+            Instructions::SetDelayTimerToVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instructions::SetSoundTimerToVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instructions::AddVxToI(x) => write!(f, "ADD I, V{:X}", x),
+            Instructions::SetIToSpriteLocation(x) => write!(f, "LD F, V{:X}", x),
+            Instructions::StoreVxAsBCDInI(x) => write!(f, "LD B, V{:X}", x),
+            Instructions::DumpRegisters(x) => write!(f, "LD [I], V{:X}", x),
+            Instructions::LoadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+            Instructions::StoreRegistersInRplFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instructions::LoadRegistersFromRplFlags(x) => write!(f, "LD V{:X}, R", x),
+            Instructions::CallMachineCode(address) => write!(f, "SYS {:#05x}", address),
+            Instructions::Unkown => write!(f, "???"),
+        }
+    }
+}
+
+// FX0A halts the CPU until a key is pressed and released, as on the COSMAC VIP. the key
+// is only stored in the register once it is released.
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyWait {
+    pub register: RegisterIdentifier,
+    pub pressed_key: Option<u8>,
+}
+
+// keys injected through the API are checked, the keypad only has the keys 0 to F
+fn check_key(key: u8) -> Result<(), String> {
+    if key < 16 {
+        Ok(())
+    } else {
+        Err(format!("invalid key {} (expected 0 to F)", key))
+    }
+}
+
+enum MovieMode {
+    Recording { movie: Movie, path: PathBuf },
+    Playing { movie: Movie, frame: usize },
+}
+
+// how the emulator is shown and controlled
+pub enum FrontendConfig {
+    Window,
+    Terminal(TerminalConfig),
+}
+
+enum Frontend {
+    Window {
+        sdl_context: sdl2::Sdl,
+        window: Box<Window>,
+        input: Box<Input>,
+        sound: Sound,
+    },
+    Terminal(Box<Terminal>),
+}
+
 pub struct Chip8 {
     data_registers: [Register; 16],
     memory: [u8; MEMORY_SIZE as usize],
@@ -89,20 +379,75 @@ pub struct Chip8 {
     stack: Vec<Address>,
     delay_timer: Arc<AtomicRegister>,
     sound_timer: Arc<AtomicRegister>,
-    thread_killer: Arc<AtomicBool>,
-    timer_thread: Option<JoinHandle<()>>,
+    frontend: Frontend,
     display: Display,
-    input: Input,
-    sound: Sound,
+    palette: Palette,
+    sprite_viewer: Option<SpriteViewer>,
+    code_write_monitor: Option<CodeWriteMonitor>,
+    linter: Option<Linter>,
+    paused: bool,
+    rewind_buffer: Option<RewindBuffer>,
+    rewinding: bool,
+    cycle: u64,
+    // like cycle, but also counting the cycles halted at FX0A. scheduled inputs are timed
+    // by it, so they can release the CPU from the halt
+    emulated_cycles: u64,
+    program_size: u16,
+    rom_hash: u64,
+    random: Random,
+    keypad: [bool; 16],
+    movie: Option<MovieMode>,
+    movie_finished: bool,
+    // the "RPL user flags" of the HP-48, used by SCHIP games to keep e.g. high scores
+    rpl_flags: [Register; 16],
+    rpl_flags_changed: bool,
+    // file name of the ROM without extension, used to name screenshots
+    rom_name: String,
+    video_recorder: Option<VideoRecorder>,
+    hud: Hud,
+    // Some while the CPU is halted at FX0A
+    key_wait: Option<KeyWait>,
+    // the title last set in the frontend
+    title: String,
+    // number of emulated frames, the time base of scheduled inputs
+    frame: u64,
+    // the cycles of the current frame executed so far
+    cycle_in_frame: u32,
+    // the keys held in the frontend and the ones pressed through the API in this frame,
+    // the keypad is the combination of both
+    host_keys: [bool; 16],
+    injected_keys: [bool; 16],
+    scheduled_inputs: Vec<ScheduledInput>,
+    // the keys checked by EX9E, EXA1 or FX0A in the current and in the last frame
+    current_polled_keys: [bool; 16],
+    polled_keys: [bool; 16],
 }
 
 impl Chip8 {
-    pub fn init() -> Chip8 {
-        // initialize sdl
-        let sdl_context = sdl2::init().expect("ERROR: Unable to initialize SDL. Exiting...");
-
+    pub fn init(frontend: FrontendConfig) -> Chip8 {
         let sound_timer = Arc::new(AtomicU8::new(0));
 
+        let frontend = match frontend {
+            FrontendConfig::Window => {
+                // initialize sdl
+                let sdl_context =
+                    sdl2::init().expect("ERROR: Unable to initialize SDL. Exiting...");
+                let window = Box::new(Window::init(&sdl_context));
+                let input = Box::new(Input::init(sdl_context.clone(), window.window_id()));
+                let sound = Sound::init(&sdl_context, sound_timer.clone());
+
+                Frontend::Window {
+                    sdl_context,
+                    window,
+                    input,
+                    sound,
+                }
+            }
+            FrontendConfig::Terminal(config) => {
+                Frontend::Terminal(Box::new(Terminal::init(config)))
+            }
+        };
+
         let mut chip = Chip8 {
             data_registers: [0; 16],
             memory: [0; MEMORY_SIZE as usize],
@@ -110,12 +455,38 @@ impl Chip8 {
             index_register: 0x00,
             stack: Vec::new(),
             delay_timer: Arc::new(AtomicU8::new(0)),
-            sound_timer: sound_timer.clone(),
-            thread_killer: Arc::new(AtomicBool::new(false)),
-            timer_thread: None,
-            display: Display::init(sdl_context.clone()),
-            input: Input::init(sdl_context.clone()),
-            sound: Sound::init(&sdl_context, sound_timer),
+            sound_timer,
+            frontend,
+            display: Display::init(),
+            palette: Palette::default(),
+            sprite_viewer: None,
+            code_write_monitor: None,
+            linter: None,
+            paused: false,
+            rewind_buffer: None,
+            rewinding: false,
+            cycle: 0,
+            emulated_cycles: 0,
+            program_size: 0,
+            rom_hash: 0,
+            random: Random::init(rand::random()),
+            keypad: [false; 16],
+            movie: None,
+            movie_finished: false,
+            rpl_flags: [0; 16],
+            rpl_flags_changed: false,
+            rom_name: String::new(),
+            video_recorder: None,
+            hud: Hud::init(),
+            key_wait: None,
+            title: String::new(),
+            frame: 0,
+            cycle_in_frame: 0,
+            host_keys: [false; 16],
+            injected_keys: [false; 16],
+            scheduled_inputs: Vec::new(),
+            current_polled_keys: [false; 16],
+            polled_keys: [false; 16],
         };
 
         chip.setup_fonts();
@@ -124,7 +495,441 @@ impl Chip8 {
     }
 
     pub fn draw_display(&mut self) {
-        self.display.draw();
+        let buffer_changed = self.display.take_dirty();
+        self.hud.count_draw(self.cycle);
+        self.update_title();
+        // the keypad panel shows the keys as they are held, or as they are played back
+        let key_states = match self.movie {
+            Some(MovieMode::Playing { .. }) => self.keypad,
+            _ => self.key_states(),
+        };
+        let polled_keys = self.polled_keys();
+
+        match &mut self.frontend {
+            Frontend::Window { window, .. } => window.draw(
+                self.display.buffer(),
+                &self.palette,
+                buffer_changed,
+                &key_states,
+                &self.hud.status_lines(self.paused, &polled_keys),
+                &self.hud.message_lines(),
+            ),
+            Frontend::Terminal(terminal) => {
+                terminal.update_beeper(self.sound_timer.load(Ordering::Relaxed) > 0);
+                terminal.draw(self.display.buffer(), &self.palette, buffer_changed);
+            }
+        }
+
+        if let Some(sprite_viewer) = &mut self.sprite_viewer {
+            sprite_viewer.draw(&self.memory, self.index_register);
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            rom_name: self.rom_name.clone(),
+            platform: status::PLATFORM,
+            speed: self.hud.speed(),
+            instructions_per_second: self.hud.instructions_per_second(),
+            paused: self.paused,
+            rewinding: self.rewinding,
+            waiting_for_key: self.key_wait.is_some(),
+        }
+    }
+
+    // the title is only set when the status changed
+    fn update_title(&mut self) {
+        let title = self.status().to_string();
+        if title == self.title {
+            return;
+        }
+
+        match &mut self.frontend {
+            Frontend::Window { window, .. } => window.set_title(&title),
+            Frontend::Terminal(terminal) => terminal.set_title(&title),
+        }
+        self.title = title;
+    }
+
+    // shows IPS, FPS and the speed in the HUD of the window frontend
+    pub fn show_statistics(&mut self, show_statistics: bool) {
+        self.hud.set_show_statistics(show_statistics);
+    }
+
+    // prints the message and shows it in the HUD for a few seconds
+    fn notify(&mut self, message: String) {
+        self.print_message(message.clone());
+        self.hud.show_message(message);
+    }
+
+    // the terminal frontend shows the message below the display, as printing it would
+    // garble the display
+    fn print_message(&mut self, message: String) {
+        match &mut self.frontend {
+            Frontend::Window { .. } => println!("{}", message),
+            Frontend::Terminal(terminal) => terminal.show_message(message),
+        }
+    }
+
+    fn print_error(&mut self, message: String) {
+        match &mut self.frontend {
+            Frontend::Window { .. } => eprintln!("{}", message),
+            Frontend::Terminal(terminal) => terminal.show_message(message),
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.display.mark_dirty();
+    }
+
+    // the following settings only apply to the window frontend
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        if let Frontend::Window { input, .. } = &mut self.frontend {
+            input.set_keymap(keymap);
+        }
+    }
+
+    pub fn show_keypad_panel(&mut self, keypad_panel: bool) {
+        if let Frontend::Window { window, .. } = &mut self.frontend {
+            window.set_keypad_panel(keypad_panel);
+        }
+    }
+
+    pub fn set_controller_mapping(&mut self, mapping: ControllerMapping) {
+        if let Frontend::Window { input, .. } = &mut self.frontend {
+            input.set_controller_mapping(mapping);
+        }
+    }
+
+    pub fn set_persistence(&mut self, mode: Persistence) {
+        if let Frontend::Window { window, .. } = &mut self.frontend {
+            window.set_persistence(mode);
+        }
+        self.display.mark_dirty();
+    }
+
+    pub fn set_filters(&mut self, filters: Vec<CrtFilter>) {
+        if let Frontend::Window { window, .. } = &mut self.frontend {
+            window.set_filters(filters);
+        }
+        self.display.mark_dirty();
+    }
+
+    pub fn set_window_scale(&mut self, scale: u32, integer_scaling: bool) {
+        if let Frontend::Window { window, .. } = &mut self.frontend {
+            window.set_scale(scale);
+            window.set_integer_scaling(integer_scaling);
+        }
+    }
+
+    // the whole-numbered scale of the display, e.g. for screenshots
+    fn window_scale(&self) -> u32 {
+        match &self.frontend {
+            Frontend::Window { window, .. } => window.window_scale(),
+            Frontend::Terminal(_) => DEFAULT_SCALE_FACTOR,
+        }
+    }
+
+    fn key_states(&self) -> [bool; 16] {
+        match &self.frontend {
+            Frontend::Window { input, .. } => input.key_states(),
+            Frontend::Terminal(terminal) => terminal.key_states(),
+        }
+    }
+
+    // whether the user asked to quit
+    fn is_quit_requested(&self) -> bool {
+        match &self.frontend {
+            Frontend::Window { input, .. } => input.should_exit(),
+            Frontend::Terminal(terminal) => terminal.should_exit(),
+        }
+    }
+
+    pub fn monitor_code_writes(&mut self, action: CodeWriteAction) {
+        self.code_write_monitor = Some(CodeWriteMonitor::init(action));
+    }
+
+    // reports portability hazards of the loaded program while it runs.
+    // memory is treated as written if it holds the fonts or the program.
+    pub fn enable_strict_mode(&mut self) {
+        let mut linter = Linter::init();
+        linter.mark_written(FONT_STARTING_MEMORY, FONTS.len() as u16);
+        linter.mark_written(PROGRAM_OFFSET, self.program_size);
+        self.linter = Some(linter);
+    }
+
+    pub fn print_strict_summary(&self) {
+        if let Some(linter) = &self.linter {
+            linter.print_summary();
+        }
+    }
+
+    fn lint(&mut self, program_counter: Address, hazard: Hazard) {
+        let cycle = self.cycle;
+        if let Some(warning) = self
+            .linter
+            .as_mut()
+            .and_then(|linter| linter.report(program_counter, cycle, hazard))
+        {
+            self.print_message(warning);
+        }
+    }
+
+    fn lint_memory_read(&mut self, program_counter: Address, start_address: Address, length: u16) {
+        let cycle = self.cycle;
+        if let Some(warning) = self
+            .linter
+            .as_mut()
+            .and_then(|linter| linter.check_read(program_counter, cycle, start_address, length))
+        {
+            self.print_message(warning);
+        }
+    }
+
+    fn lint_stack_depth(&mut self, program_counter: Address) {
+        let (cycle, depth) = (self.cycle, self.stack.len());
+        if let Some(warning) = self
+            .linter
+            .as_mut()
+            .and_then(|linter| linter.check_stack_depth(program_counter, cycle, depth))
+        {
+            self.print_message(warning);
+        }
+    }
+
+    fn lint_memory_write(&mut self, start_address: Address, length: u16) {
+        if let Some(linter) = &mut self.linter {
+            linter.mark_written(start_address, length);
+        }
+    }
+
+    pub fn snapshot(&self) -> MachineState {
+        let mut display_buffer = [false; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize];
+        display_buffer.copy_from_slice(self.display.buffer());
+
+        MachineState {
+            rom_hash: self.rom_hash,
+            data_registers: self.data_registers,
+            memory: self.memory,
+            program_counter: self.program_counter,
+            index_register: self.index_register,
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer.load(Ordering::Relaxed),
+            sound_timer: self.sound_timer.load(Ordering::Relaxed),
+            random_state: self.random.state(),
+            display_buffer,
+            key_states: self.keypad,
+            key_wait: self.key_wait,
+            rpl_flags: self.rpl_flags,
+        }
+    }
+
+    pub fn restore(&mut self, state: &MachineState) -> Result<(), SaveStateError> {
+        if state.rom_hash != self.rom_hash {
+            return Err(SaveStateError::RomMismatch);
+        }
+
+        self.data_registers = state.data_registers;
+        self.memory = state.memory;
+        self.program_counter = state.program_counter;
+        self.index_register = state.index_register;
+        self.stack = state.stack.clone();
+        self.delay_timer.store(state.delay_timer, Ordering::Relaxed);
+        self.sound_timer.store(state.sound_timer, Ordering::Relaxed);
+        self.random = Random::init(state.random_state);
+        self.display.set_buffer(&state.display_buffer);
+        self.keypad = state.key_states;
+        self.key_wait = state.key_wait;
+        self.rpl_flags = state.rpl_flags;
+
+        Ok(())
+    }
+
+    pub fn load_state_from_file(&mut self, path: &Path) -> Result<(), SaveStateError> {
+        self.restore(&MachineState::read_from_file(path)?)
+    }
+
+    fn save_state_to_slot(&mut self, slot: u8) {
+        let path = user_data::state_slot_path(self.rom_hash, slot);
+        match self.snapshot().write_to_file(&path) {
+            Ok(()) => self.notify(format!("State saved to slot {}", slot)),
+            Err(err) => self.print_error(format!(
+                "ERROR: Could not save state to slot {}: {}",
+                slot, err
+            )),
+        }
+    }
+
+    fn load_state_from_slot(&mut self, slot: u8) {
+        if self.movie.is_some() {
+            self.print_error(
+                "ERROR: States can not be loaded while a movie is recorded or played".to_string(),
+            );
+            return;
+        }
+
+        let path = user_data::state_slot_path(self.rom_hash, slot);
+        match self.load_state_from_file(&path) {
+            Ok(()) => self.notify(format!("State loaded from slot {}", slot)),
+            Err(err) => self.print_error(format!(
+                "ERROR: Could not load state from slot {}: {}",
+                slot, err
+            )),
+        }
+    }
+
+    // when the emulator is closed mid-game, the session is suspended into a resume state
+    // that is offered on the next start of the same ROM
+    pub fn has_resume_state(&self) -> bool {
+        user_data::resume_state_path(self.rom_hash).exists()
+    }
+
+    pub fn resume_session(&mut self) -> Result<(), SaveStateError> {
+        let path = user_data::resume_state_path(self.rom_hash);
+        self.load_state_from_file(&path)?;
+        self.clear_resume_state();
+        Ok(())
+    }
+
+    pub fn clear_resume_state(&self) {
+        let path = user_data::resume_state_path(self.rom_hash);
+        if let Err(err) = std::fs::remove_file(&path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                eprintln!(
+                    "ERROR: Could not remove resume state {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    // only sessions closed by the user are suspended, not finished movie playbacks
+    pub fn suspend_session(&self) {
+        if !self.is_quit_requested() || self.movie.is_some() {
+            return;
+        }
+
+        let path = user_data::resume_state_path(self.rom_hash);
+        match self.snapshot().write_to_file(&path) {
+            Ok(()) => println!("Session suspended, it will be offered on the next start"),
+            Err(err) => eprintln!("ERROR: Could not suspend session: {}", err),
+        }
+    }
+
+    fn handle_hotkey(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => self.rewinding = self.rewind_buffer.is_some() && self.movie.is_none(),
+            Event::KeyUp {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => self.rewinding = false,
+            _ => (),
+        }
+
+        if let Event::KeyDown {
+            keycode: Some(keycode),
+            keymod,
+            repeat: false,
+            ..
+        } = *event
+        {
+            let state_slot = match keycode {
+                Keycode::F1 => Some(1),
+                Keycode::F2 => Some(2),
+                Keycode::F3 => Some(3),
+                Keycode::F4 => Some(4),
+                Keycode::F5 => Some(5),
+                Keycode::F6 => Some(6),
+                Keycode::F7 => Some(7),
+                Keycode::F8 => Some(8),
+                Keycode::F9 => Some(9),
+                Keycode::F10 => Some(10),
+                _ => None,
+            };
+
+            // F1-F10 save to the according slot, with shift held they load from it
+            if let Some(slot) = state_slot {
+                if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                    self.load_state_from_slot(slot);
+                } else {
+                    self.save_state_to_slot(slot);
+                }
+            } else if keycode == Keycode::F11 {
+                self.hud.toggle_statistics();
+            } else if keycode == Keycode::F12 {
+                self.take_screenshot();
+            } else if keycode == Keycode::P {
+                self.toggle_pause();
+            } else if keycode == Keycode::Return && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+                if let Frontend::Window { window, .. } = &mut self.frontend {
+                    window.toggle_fullscreen();
+                }
+            }
+        }
+    }
+
+    pub fn take_screenshot(&mut self) {
+        match save_screenshot(
+            Path::new(SCREENSHOT_DIRECTORY),
+            &self.rom_name,
+            self.display.buffer(),
+            &self.palette,
+            self.window_scale(),
+        ) {
+            Ok(paths) => {
+                for path in paths {
+                    self.notify(format!("Screenshot saved to {}", path.display()));
+                }
+            }
+            Err(err) => self.print_error(format!("ERROR: Could not save screenshot: {}", err)),
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        let message = if self.paused { "Paused" } else { "Resumed" };
+        self.notify(message.to_string());
+    }
+
+    // returns true if the emulation should break
+    fn check_code_write(
+        &mut self,
+        program_counter: Address,
+        instruction: &Instructions,
+        length: u16,
+    ) -> bool {
+        let Some(monitor) = &self.code_write_monitor else {
+            return false;
+        };
+        let Some(warning) =
+            monitor.check_write(program_counter, instruction, self.index_register, length)
+        else {
+            return false;
+        };
+
+        let breaks = monitor.breaks();
+        self.print_message(warning);
+        if breaks {
+            self.print_message("Emulation paused, press P to continue".to_string());
+        }
+        breaks
+    }
+
+    pub fn open_sprite_viewer(&mut self, config: SpriteViewerConfig) {
+        let Frontend::Window { sdl_context, .. } = &self.frontend else {
+            eprintln!("ERROR: The sprite-viewer is only available in the window frontend");
+            return;
+        };
+        let video_subsystem = sdl_context
+            .video()
+            .expect("ERROR: Could not initialize video-subsystem. Exiting...");
+
+        self.sprite_viewer = Some(SpriteViewer::init(&video_subsystem, config));
     }
 
     fn setup_fonts(&mut self) {
@@ -133,177 +938,381 @@ impl Chip8 {
         }
     }
 
+    // the terminal frontend rings the bell instead
     pub fn start_sound_system(&self) {
-        self.sound.start_sound_system();
+        if let Frontend::Window { sound, .. } = &self.frontend {
+            sound.start_sound_system();
+        }
     }
 
     pub fn stop_sound_system(&self) {
-        self.sound.stop_sound_system();
+        if let Frontend::Window { sound, .. } = &self.frontend {
+            sound.stop_sound_system();
+        }
     }
 
-    pub fn stop_timers(&mut self) {
-        self.thread_killer.store(true, Ordering::Relaxed);
-        self.timer_thread.take().map(JoinHandle::join);
+    // the timers are decremented at 60Hz, i.e. once per emulated frame
+    fn tick_timers(&mut self) {
+        for timer in [&self.delay_timer, &self.sound_timer] {
+            let _ = timer.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+                value.checked_sub(1)
+            });
+        }
+    }
+
+    // presses or releases a key as if it was held in the frontend. it stays pressed until
+    // it is released the same way.
+    pub fn set_injected_key(&mut self, key: u8, pressed: bool) -> Result<(), String> {
+        check_key(key)?;
+        self.injected_keys[key as usize] = pressed;
+        // a movie being played back decides the keypad alone
+        if !matches!(self.movie, Some(MovieMode::Playing { .. })) {
+            self.keypad[key as usize] = pressed || self.host_keys[key as usize];
+        }
+        Ok(())
     }
 
-    pub fn start_timers(&mut self) {
-        let thread_killer = self.thread_killer.clone();
-        let delay_timer = self.delay_timer.clone();
-        let sound_timer = self.sound_timer.clone();
+    // injects the key press or release at the given frame or cycle
+    pub fn schedule_input(&mut self, input: ScheduledInput) -> Result<(), String> {
+        check_key(input.key)?;
+        self.scheduled_inputs.push(input);
+        Ok(())
+    }
+
+    fn apply_scheduled_inputs(&mut self) {
+        if self.scheduled_inputs.is_empty() {
+            return;
+        }
+
+        let is_due = |input: &ScheduledInput, frame: u64, cycle: u64| match input.time {
+            InputTime::Frame(at) => at <= frame,
+            InputTime::Cycle(at) => at <= cycle,
+        };
+        let (due, pending) = self
+            .scheduled_inputs
+            .iter()
+            .partition(|input| is_due(input, self.frame, self.emulated_cycles));
+        self.scheduled_inputs = pending;
+
+        // the keys were checked when the inputs were scheduled
+        for input in due {
+            self.set_injected_key(input.key, input.pressed).ok();
+        }
+    }
+
+    // the keys the ROM checked during the last frame, all of them while it waits at FX0A
+    pub fn polled_keys(&self) -> [bool; 16] {
+        self.polled_keys
+    }
+
+    // spreads the cycles of a second evenly over its frames. this depends on the emulated
+    // frames only, so that movies are played back with the cycles they were recorded with.
+    fn cycles_in_frame(&self, frequency: u32) -> u32 {
+        let frequency = frequency as u64;
+        let frame_in_second = self.frame % FRAMES_PER_SECOND;
+        ((frame_in_second + 1) * frequency / FRAMES_PER_SECOND
+            - frame_in_second * frequency / FRAMES_PER_SECOND) as u32
+    }
+
+    // sets the keypad before the given cycle of the current frame, either from the movie
+    // being played back or from the scheduled inputs. a recorded movie gets every change.
+    fn update_keypad(&mut self, cycle: u32) {
+        if let Some(MovieMode::Playing { movie, frame }) = &self.movie {
+            self.keypad = movie.keypad(*frame, cycle);
+            return;
+        }
+
+        self.apply_scheduled_inputs();
+        if let Some(MovieMode::Recording { movie, .. }) = &mut self.movie {
+            movie.record_keypad(cycle, &self.keypad);
+        }
+    }
+
+    // emulates one frame (1/60s) worth of cycles at the given frequency and ticks the timers
+    // once. while the rewind key is held, the frame is instead replaced by the previously
+    // recorded one. a frame interrupted by a break is continued after the pause.
+    pub fn emulate_frame(&mut self, frequency: u32) {
+        if self.paused {
+            return;
+        }
 
-        self.timer_thread = Some(std::thread::spawn(move || {
-            while !thread_killer.load(Ordering::Relaxed) {
-                // TODO: these operations are not atomic. For now ignore this...
-                // TODO: but you will want to use something like fetch_update...
-                if delay_timer.load(Ordering::Relaxed) > 0 {
-                    delay_timer.fetch_sub(1, Ordering::Relaxed);
+        if self.rewinding {
+            self.rewind_frame();
+            return;
+        }
+
+        match &self.movie {
+            Some(MovieMode::Playing { movie, frame }) => {
+                if *frame >= movie.frame_count() {
+                    self.finish_movie();
+                    return;
                 }
-                if sound_timer.load(Ordering::Relaxed) > 0 {
-                    sound_timer.fetch_sub(1, Ordering::Relaxed);
+            }
+            _ => {
+                self.host_keys = self.key_states();
+                for key in 0..16 {
+                    self.keypad[key] = self.host_keys[key] || self.injected_keys[key];
                 }
+            }
+        }
 
-                std::thread::sleep(std::time::Duration::new(0, 16666667));
+        let cycles = self.cycles_in_frame(frequency);
+        while self.cycle_in_frame < cycles {
+            self.update_keypad(self.cycle_in_frame);
+            self.emulate_cycle();
+            self.cycle_in_frame += 1;
+            if self.paused {
+                return;
             }
-        }));
+        }
+        self.cycle_in_frame = 0;
+
+        match &mut self.movie {
+            Some(MovieMode::Recording { movie, .. }) => movie.finish_frame(),
+            Some(MovieMode::Playing { frame, .. }) => *frame += 1,
+            None => (),
+        }
+        self.frame += 1;
+        self.polled_keys = self.current_polled_keys;
+        self.current_polled_keys = [false; 16];
+        self.hud.count_frame();
+        self.record_video_frame();
+        self.tick_timers();
+
+        let is_snapshot_due = self
+            .rewind_buffer
+            .as_mut()
+            .is_some_and(RewindBuffer::is_snapshot_due);
+        // a stack too deep to be stored can't be rewound to
+        if is_snapshot_due {
+            if let (Ok(state), Some(rewind_buffer)) =
+                (self.snapshot().encode(), &mut self.rewind_buffer)
+            {
+                rewind_buffer.push(state);
+            }
+        }
+    }
+
+    // a state with a stack too deep to be stored hashes like an empty one
+    fn state_hash(&self) -> u64 {
+        user_data::hash(&self.snapshot().encode().unwrap_or_default())
+    }
+
+    // records every change of the keypad from now on. the random number generator is
+    // reseeded, so the seed can be stored in the movie together with the RPL user flags.
+    pub fn record_movie(&mut self, path: &Path, frequency: u32) {
+        let seed = rand::random();
+        self.random = Random::init(seed);
+        self.movie = Some(MovieMode::Recording {
+            movie: Movie::init(self.rom_hash, seed, frequency, self.rpl_flags),
+            path: path.to_path_buf(),
+        });
+    }
+
+    // replaces the keypad and the RPL user flags by the ones recorded in the movie. returns
+    // the frequency the movie was recorded with, which must be used for the playback.
+    pub fn play_movie(&mut self, path: &Path) -> Result<u32, MovieError> {
+        let movie = Movie::read_from_file(path)?;
+        if movie.rom_hash != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+
+        let frequency = movie.frequency;
+        self.random = Random::init(movie.seed);
+        self.rpl_flags = movie.rpl_flags;
+        self.movie = Some(MovieMode::Playing { movie, frame: 0 });
+        Ok(frequency)
+    }
+
+    // stores a recorded movie or verifies a played one against the final state
+    pub fn finish_movie(&mut self) {
+        let state_hash = self.state_hash();
+
+        match self.movie.take() {
+            Some(MovieMode::Recording { mut movie, path }) => {
+                movie.final_state_hash = state_hash;
+                match movie.write_to_file(&path) {
+                    Ok(()) => println!(
+                        "Recorded {} frames to {}",
+                        movie.frame_count(),
+                        path.display()
+                    ),
+                    Err(err) => eprintln!(
+                        "ERROR: Could not write movie to {}: {}",
+                        path.display(),
+                        err
+                    ),
+                }
+            }
+            Some(MovieMode::Playing { movie, frame }) => {
+                if frame < movie.frame_count() {
+                    println!(
+                        "Playback stopped at frame {} of {}",
+                        frame,
+                        movie.frame_count()
+                    );
+                } else if state_hash == movie.final_state_hash {
+                    println!("Playback of {} frames verified: final state matches", frame);
+                } else {
+                    println!(
+                        "Playback of {} frames DIVERGED: final state hash {:016x}, expected {:016x}",
+                        frame, state_hash, movie.final_state_hash
+                    );
+                }
+                self.movie_finished = true;
+                // the flags of a replayed run are not kept for the ROM
+                self.rpl_flags_changed = false;
+            }
+            None => (),
+        }
+    }
+
+    pub fn record_video(&mut self, path: &Path, scale: u32) -> std::io::Result<()> {
+        self.video_recorder = Some(VideoRecorder::create(path, &self.palette, scale)?);
+        Ok(())
+    }
+
+    // captures the display and the beeper of the frame emulated last
+    fn record_video_frame(&mut self) {
+        let Some(recorder) = &mut self.video_recorder else {
+            return;
+        };
+
+        let is_beeping = self.sound_timer.load(Ordering::Relaxed) > 0;
+        if let Err(err) = recorder.record_frame(self.display.buffer(), is_beeping) {
+            self.video_recorder = None;
+            self.print_error(format!(
+                "ERROR: Could not record video frame, recording stopped: {}",
+                err
+            ));
+        }
+    }
+
+    pub fn finish_video(&mut self) {
+        if let Some(recorder) = self.video_recorder.take() {
+            let frame_count = recorder.frame_count();
+            match recorder.finish() {
+                Ok(()) => println!("Recorded {} frames of video", frame_count),
+                Err(err) => eprintln!("ERROR: Could not finish video recording: {}", err),
+            }
+        }
+    }
+
+    pub fn enable_rewind(&mut self, frames: usize, granularity: u32) {
+        self.rewind_buffer = Some(RewindBuffer::init(
+            frames / granularity.max(1) as usize,
+            granularity,
+        ));
+    }
+
+    fn rewind_frame(&mut self) {
+        let is_pop_due = self
+            .rewind_buffer
+            .as_mut()
+            .is_some_and(RewindBuffer::is_pop_due);
+        if !is_pop_due {
+            return;
+        }
+
+        let state = match self.rewind_buffer.as_mut().and_then(RewindBuffer::pop) {
+            Some(state) => state,
+            None => return,
+        };
+
+        let state = MachineState::decode(&state).expect("ERROR: Corrupt rewind buffer. Exiting...");
+        self.restore(&state)
+            .expect("ERROR: Corrupt rewind buffer. Exiting...");
     }
 
     pub fn process_events(&mut self) {
-        self.input.process_all_events();
+        let (events, window_id) = match &mut self.frontend {
+            Frontend::Window { window, input, .. } => (
+                input.process_all_events(window.keypad_area()),
+                window.window_id(),
+            ),
+            Frontend::Terminal(terminal) => {
+                terminal.process_input();
+                return;
+            }
+        };
+
+        for event in events {
+            if event.get_window_id() == Some(window_id) {
+                self.handle_hotkey(&event);
+                continue;
+            }
+
+            if let Some(sprite_viewer) = &mut self.sprite_viewer {
+                if event.get_window_id() != Some(sprite_viewer.window_id()) {
+                    continue;
+                }
+
+                if let Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                } = event
+                {
+                    self.sprite_viewer = None;
+                } else {
+                    sprite_viewer.handle_event(&event, &self.memory);
+                }
+            }
+        }
     }
 
     pub fn should_exit(&mut self) -> bool {
-        self.input.should_exit()
-    }
-
-    fn decode(&self, instruction: u16) -> Instructions {
-        if instruction == 0x00e0 {
-            return Instructions::ClearScreen;
-        } else if instruction & 0xf000 == 0x1000 {
-            return Instructions::UnconditionalJump(instruction & 0x0fff);
-        } else if instruction & 0xf000 == 0x6000 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            let intermediate = instruction as u8;
-            return Instructions::SetVxToIntermediate(register_identifier, intermediate);
-        } else if instruction & 0xf000 == 0x7000 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            let intermediate = instruction as u8;
-            return Instructions::AddIntermediateToVx(register_identifier, intermediate);
-        } else if instruction & 0xf000 == 0xa000 {
-            let address = instruction & 0x0fff;
-            return Instructions::SetIndexRegisterToIntermediate(address);
-        } else if instruction & 0xf000 == 0xd000 {
-            let x_coord = self.data_registers[((instruction & 0x0f00) >> 8) as usize];
-            let y_coord = self.data_registers[((instruction & 0x00f0) >> 4) as usize];
-            let height = (instruction & 0x000f) as u8;
-            return Instructions::DrawSprite(x_coord, y_coord, height);
-        } else if instruction & 0xf0ff == 0xe09e {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SkipIfKeyPressedVx(register_identifier);
-        } else if instruction & 0xf0ff == 0xe0a1 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SkipIfKeyNotPressedVx(register_identifier);
-        } else if instruction & 0xf0ff == 0xf00a {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::AwaitKeyPressVx(register_identifier);
-        } else if instruction == 0x00ee {
-            return Instructions::ReturnFromSubroutine;
-        } else if instruction & 0xf000 == 0x2000 {
-            let address = instruction & 0x0fff;
-            return Instructions::CallSubroutine(address);
-        } else if instruction & 0xf000 == 0x3000 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            let intermediate = instruction as u8;
-            return Instructions::SkipIfVxIsIntermediate(register_identifier, intermediate);
-        } else if instruction & 0xf000 == 0x4000 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            let intermediate = instruction as u8;
-            return Instructions::SkipIfVxIsNotIntermediate(register_identifier, intermediate);
-        } else if instruction & 0xf00f == 0x5000 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::SkipIfVxIsVy(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8000 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::SetVxToVy(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8001 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::BitwiseOrVyToVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8002 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::BitwiseAndVyToVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8003 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::BitwiseXorVyToVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8004 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::AddVyToVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8005 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::SubtractVyFromVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x8006 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::StoreLSBfromVxInVf(register_identifier);
-        } else if instruction & 0xf00f == 0x800E {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::StoreMSBfromVxInVf(register_identifier);
-        } else if instruction & 0xf00f == 0x8007 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::SetVxToVyMinusVx(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf00f == 0x9000 {
-            let register_identifier_x = ((instruction & 0x0f00) >> 8) as u8;
-            let register_identifier_y = ((instruction & 0x00f0) >> 4) as u8;
-            return Instructions::SkipIfVxIsNotVy(register_identifier_x, register_identifier_y);
-        } else if instruction & 0xf000 == 0xb000 {
-            let address = instruction & 0x0fff;
-            return Instructions::UnconditionalJumpWithOffset(address);
-        } else if instruction & 0xf000 == 0xc000 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            let intermediate = instruction as u8;
-            return Instructions::GenerateRandomNumberWithCap(register_identifier, intermediate);
-        } else if instruction & 0xf0ff == 0xf007 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SetVxToDelayTimer(register_identifier);
-        } else if instruction & 0xf0ff == 0xf018 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SetSoundTimerToVx(register_identifier);
-        } else if instruction & 0xf0ff == 0xf01e {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::AddVxToI(register_identifier);
-        } else if instruction & 0xf0ff == 0xf029 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SetIToSpriteLocation(register_identifier);
-        } else if instruction & 0xf0ff == 0xf033 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::StoreVxAsBCDInI(register_identifier);
-        } else if instruction & 0xf0ff == 0xf055 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::DumpRegisters(register_identifier);
-        } else if instruction & 0xf0ff == 0xf065 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::LoadRegisters(register_identifier);
-        } else if instruction & 0xf0ff == 0xf015 {
-            let register_identifier = ((instruction & 0x0f00) >> 8) as u8;
-            return Instructions::SetDelayTimerToVx(register_identifier);
-        }
-
-        Instructions::Unkown
+        self.is_quit_requested() || self.movie_finished
+    }
+
+    // returns true while the CPU stays halted at FX0A
+    fn wait_for_key(&mut self) -> bool {
+        let Some(key_wait) = &mut self.key_wait else {
+            return false;
+        };
+        self.current_polled_keys = [true; 16];
+
+        match key_wait.pressed_key {
+            None => {
+                key_wait.pressed_key = self
+                    .keypad
+                    .iter()
+                    .position(|&is_pressed| is_pressed)
+                    .map(|key| key as u8);
+                true
+            }
+            Some(key) if self.keypad[key as usize] => true,
+            Some(key) => {
+                self.data_registers[key_wait.register as usize] = key;
+                self.key_wait = None;
+                false
+            }
+        }
     }
 
     // this is the the whole fetch, decode and execute circle:
     pub fn emulate_cycle(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.emulated_cycles += 1;
+        if self.wait_for_key() {
+            return;
+        }
+
+        let instruction_address = self.program_counter;
         let instruction = ((self.memory[self.program_counter as usize] as u16) << 8)
             + (self.memory[(self.program_counter + 1) as usize]) as u16;
 
         self.program_counter += 2;
+        self.cycle += 1;
 
-        match self.decode(instruction) {
+        if let Some(monitor) = &mut self.code_write_monitor {
+            monitor.record_execution(instruction_address);
+        }
+        self.lint_memory_read(instruction_address, instruction_address, 2);
+
+        let decoded_instruction = decode(instruction);
+        match decoded_instruction {
             Instructions::ClearScreen => {
                 self.display.clear_screen();
             }
@@ -321,10 +1330,19 @@ impl Chip8 {
             Instructions::SetIndexRegisterToIntermediate(address) => {
                 self.index_register = address;
             }
-            Instructions::DrawSprite(x_coord, y_coord, height) => {
+            Instructions::DrawSprite(register_identifier_x, register_identifier_y, height) => {
+                if self.linter.is_some() {
+                    self.lint_sprite(
+                        instruction_address,
+                        register_identifier_x,
+                        register_identifier_y,
+                        height,
+                    );
+                }
+
                 let was_turned_off = self.display.blend_sprite(
-                    x_coord,
-                    y_coord,
+                    self.data_registers[register_identifier_x as usize],
+                    self.data_registers[register_identifier_y as usize],
                     height,
                     self.index_register,
                     &self.memory,
@@ -333,25 +1351,25 @@ impl Chip8 {
                 self.data_registers[0xf] = if was_turned_off { 1 } else { 0 };
             }
             Instructions::SkipIfKeyPressedVx(register_identifier) => {
-                if self
-                    .input
-                    .is_key_pressed(self.data_registers[register_identifier as usize])
-                {
+                let key = self.data_registers[register_identifier as usize] as usize;
+                if self.keypad[key] {
                     self.program_counter += 2;
                 }
+                self.current_polled_keys[key] = true;
             }
             Instructions::SkipIfKeyNotPressedVx(register_identifier) => {
-                if !self
-                    .input
-                    .is_key_pressed(self.data_registers[register_identifier as usize])
-                {
+                let key = self.data_registers[register_identifier as usize] as usize;
+                if !self.keypad[key] {
                     self.program_counter += 2;
                 }
+                self.current_polled_keys[key] = true;
             }
             Instructions::AwaitKeyPressVx(register_identifier) => {
-                // On the original COSMAC VIP, the key was only registered when it was pressed and then released.
-                // currently this is not the case: if the key is pressed down, function will return true!
-                self.data_registers[register_identifier as usize] = self.input.get_key_blocking();
+                // the CPU halts until a key is pressed and released, see wait_for_key
+                self.key_wait = Some(KeyWait {
+                    register: register_identifier,
+                    pressed_key: None,
+                });
             }
             Instructions::ReturnFromSubroutine => {
                 self.program_counter = self.stack.pop().expect(
@@ -361,6 +1379,8 @@ impl Chip8 {
             Instructions::CallSubroutine(address) => {
                 self.stack.push(self.program_counter);
                 self.program_counter = address;
+
+                self.lint_stack_depth(instruction_address);
             }
             Instructions::SkipIfVxIsIntermediate(register_identifier, intermediate) => {
                 if self.data_registers[register_identifier as usize] == intermediate {
@@ -396,12 +1416,18 @@ impl Chip8 {
                     self.data_registers[register_identifier_y as usize];
             }
             Instructions::AddVyToVx(register_identifier_x, register_identifier_y) => {
+                if register_identifier_x == 0xf || register_identifier_y == 0xf {
+                    self.lint(instruction_address, Hazard::VfAsOperand);
+                }
                 let (result, did_overflow) = self.data_registers[register_identifier_x as usize]
                     .overflowing_add(self.data_registers[register_identifier_y as usize]);
                 self.data_registers[register_identifier_x as usize] = result;
                 self.data_registers[0xf] = if did_overflow { 1 } else { 0 };
             }
             Instructions::SubtractVyFromVx(register_identifier_x, register_identifier_y) => {
+                if register_identifier_x == 0xf || register_identifier_y == 0xf {
+                    self.lint(instruction_address, Hazard::VfAsOperand);
+                }
                 let (result, did_underflow) = self.data_registers[register_identifier_x as usize]
                     .overflowing_sub(self.data_registers[register_identifier_y as usize]);
                 self.data_registers[register_identifier_x as usize] = result;
@@ -433,7 +1459,7 @@ impl Chip8 {
             }
             Instructions::GenerateRandomNumberWithCap(register_identifier, intermediate) => {
                 self.data_registers[register_identifier as usize] =
-                    rand::random::<u8>() & intermediate;
+                    self.random.next_u8() & intermediate;
             }
             Instructions::SetVxToDelayTimer(register_identifier) => {
                 self.data_registers[register_identifier as usize] =
@@ -449,6 +1475,7 @@ impl Chip8 {
                 self.index_register += self.data_registers[register_identifier as usize] as u16;
 
                 if self.index_register >= 0x1000 {
+                    self.lint(instruction_address, Hazard::IndexOverflowIntoVf);
                     self.index_register &= 0x0fff;
                     self.data_registers[0xf] = 1;
                 } else {
@@ -463,17 +1490,31 @@ impl Chip8 {
                 let hundreds = self.data_registers[register_identifier as usize] / 100;
                 let tens = (self.data_registers[register_identifier as usize] % 100) / 10;
                 let ones = self.data_registers[register_identifier as usize] % 10;
+                if self.check_code_write(instruction_address, &decoded_instruction, 3) {
+                    self.paused = true;
+                }
+                self.lint_memory_write(self.index_register, 3);
                 self.memory[self.index_register as usize + 0] = hundreds;
                 self.memory[self.index_register as usize + 1] = tens;
                 self.memory[self.index_register as usize + 2] = ones;
             }
             Instructions::DumpRegisters(register_identifier) => {
+                let length = register_identifier as u16 + 1;
+                if self.check_code_write(instruction_address, &decoded_instruction, length) {
+                    self.paused = true;
+                }
+                self.lint_memory_write(self.index_register, length);
                 for reg_offset in 0..register_identifier + 1 {
                     self.memory[self.index_register as usize + reg_offset as usize] =
                         self.data_registers[reg_offset as usize];
                 }
             }
             Instructions::LoadRegisters(register_identifier) => {
+                self.lint_memory_read(
+                    instruction_address,
+                    self.index_register,
+                    register_identifier as u16 + 1,
+                );
                 for reg_offset in 0..register_identifier + 1 {
                     self.data_registers[reg_offset as usize] =
                         self.memory[self.index_register as usize + reg_offset as usize];
@@ -485,6 +1526,19 @@ impl Chip8 {
                     Ordering::Relaxed,
                 );
             }
+            Instructions::StoreRegistersInRplFlags(register_identifier) => {
+                let length = register_identifier as usize + 1;
+                self.rpl_flags[..length].copy_from_slice(&self.data_registers[..length]);
+                self.rpl_flags_changed = true;
+            }
+            Instructions::LoadRegistersFromRplFlags(register_identifier) => {
+                let length = register_identifier as usize + 1;
+                self.data_registers[..length].copy_from_slice(&self.rpl_flags[..length]);
+            }
+            Instructions::CallMachineCode(address) => {
+                // there is no machine code to run, so like most interpreters ignore it
+                self.lint(instruction_address, Hazard::MachineCodeCall(address));
+            }
             Instructions::Unkown => {
                 panic!(
                     "ERROR: Given instruction: {:#06x} is not known to the emulator.",
@@ -494,6 +1548,54 @@ impl Chip8 {
         }
     }
 
+    fn lint_sprite(
+        &mut self,
+        program_counter: Address,
+        register_identifier_x: RegisterIdentifier,
+        register_identifier_y: RegisterIdentifier,
+        height: u8,
+    ) {
+        if self.index_register as u32 + height as u32 > MEMORY_SIZE as u32 {
+            self.lint(program_counter, Hazard::SpriteOutsideMemory);
+        } else {
+            self.lint_memory_read(program_counter, self.index_register, height as u16);
+        }
+
+        let x_coord = self.data_registers[register_identifier_x as usize] as u32 % DISPLAY_WIDTH;
+        let y_coord = self.data_registers[register_identifier_y as usize] as u32 % DISPLAY_HEIGHT;
+        if x_coord + 8 > DISPLAY_WIDTH || y_coord + height as u32 > DISPLAY_HEIGHT {
+            self.lint(program_counter, Hazard::SpritePartiallyOffscreen);
+        }
+    }
+
+    // the RPL user flags survive restarts like on the calculator: they are kept per ROM
+    fn load_rpl_flags(&mut self) {
+        let path = user_data::rpl_flags_path(self.rom_hash);
+        if let Ok(flags) = std::fs::read(path) {
+            let length = flags.len().min(self.rpl_flags.len());
+            self.rpl_flags[..length].copy_from_slice(&flags[..length]);
+        }
+    }
+
+    pub fn save_rpl_flags(&self) {
+        if !self.rpl_flags_changed {
+            return;
+        }
+
+        let path = user_data::rpl_flags_path(self.rom_hash);
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, self.rpl_flags));
+        if let Err(err) = result {
+            eprintln!(
+                "ERROR: Could not save RPL user flags to {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
     pub fn load_program(&mut self, path: &str) {
         let contents =
             std::fs::read(path).expect("ERROR: Could not load chip8 program. Exiting...");
@@ -501,8 +1603,83 @@ impl Chip8 {
         for i in 0..contents.len() {
             self.memory[i + PROGRAM_OFFSET as usize] = contents[i];
         }
+        self.program_size = contents.len() as u16;
+        self.rom_hash = user_data::hash(&contents);
+        self.rom_name = Path::new(path)
+            .file_stem()
+            .map_or("rom".to_string(), |name| name.to_string_lossy().to_string());
+        self.load_rpl_flags();
 
         // start execution by memory-offset:
         self.program_counter = PROGRAM_OFFSET as u16;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // a chip8 in the terminal frontend, which doesn't touch the terminal until it draws
+    fn chip8_with_program(program: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::init(FrontendConfig::Terminal(TerminalConfig {
+            keys: "1234qwerasdfzxcv".chars().collect(),
+            key_release_timeout: Duration::from_millis(150),
+        }));
+        let start = PROGRAM_OFFSET as usize;
+        chip8.memory[start..start + program.len()].copy_from_slice(program);
+        chip8.program_counter = PROGRAM_OFFSET;
+        chip8
+    }
+
+    #[test]
+    fn decodes_zero_as_unknown() {
+        assert!(matches!(decode(0x0000), Instructions::Unkown));
+        assert!(matches!(
+            decode(0x0123),
+            Instructions::CallMachineCode(0x123)
+        ));
+    }
+
+    #[test]
+    fn scheduled_cycles_release_the_key_wait() {
+        // LD V0, K; LD V1, 1; JP 0x204
+        let mut chip8 = chip8_with_program(&[0xf0, 0x0a, 0x61, 0x01, 0x12, 0x04]);
+        for (at, pressed) in [(5, true), (10, false)] {
+            chip8
+                .schedule_input(ScheduledInput {
+                    time: InputTime::Cycle(at),
+                    key: 7,
+                    pressed,
+                })
+                .unwrap();
+        }
+
+        for _ in 0..20 {
+            chip8.apply_scheduled_inputs();
+            chip8.emulate_cycle();
+        }
+
+        assert!(chip8.key_wait.is_none());
+        assert_eq!(chip8.data_registers[0], 7);
+        assert_eq!(chip8.data_registers[1], 1);
+        assert!(chip8.scheduled_inputs.is_empty());
+    }
+
+    #[test]
+    fn rejects_keys_outside_the_keypad() {
+        let mut chip8 = chip8_with_program(&[]);
+
+        assert!(chip8.set_injected_key(0xf, true).is_ok());
+        assert!(chip8.keypad[0xf]);
+        assert!(chip8.set_injected_key(16, true).is_err());
+        assert!(chip8
+            .schedule_input(ScheduledInput {
+                time: InputTime::Frame(0),
+                key: 0xff,
+                pressed: true,
+            })
+            .is_err());
+        assert!(chip8.scheduled_inputs.is_empty());
+    }
+}
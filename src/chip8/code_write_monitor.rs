@@ -0,0 +1,101 @@
+use super::{Address, Instructions, MEMORY_SIZE, PROGRAM_OFFSET};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CodeWriteAction {
+    Warn,
+    Break,
+}
+
+// keeps track of which bytes have been executed, so writes into code (self-modifying
+// code or a bad I in FX55/FX33) and into the font/interpreter region can be reported
+pub struct CodeWriteMonitor {
+    action: CodeWriteAction,
+    executed: [bool; MEMORY_SIZE as usize],
+}
+
+impl CodeWriteMonitor {
+    pub fn init(action: CodeWriteAction) -> CodeWriteMonitor {
+        CodeWriteMonitor {
+            action,
+            executed: [false; MEMORY_SIZE as usize],
+        }
+    }
+
+    pub fn record_execution(&mut self, address: Address) {
+        self.executed[address as usize] = true;
+        self.executed[(address as usize + 1) % MEMORY_SIZE as usize] = true;
+    }
+
+    // whether the emulation should break on a reported write
+    pub fn breaks(&self) -> bool {
+        self.action == CodeWriteAction::Break
+    }
+
+    // checks a write of length bytes at start_address by the instruction at
+    // program_counter and returns the warning to print if it writes into code
+    pub fn check_write(
+        &self,
+        program_counter: Address,
+        instruction: &Instructions,
+        start_address: Address,
+        length: u16,
+    ) -> Option<String> {
+        let end_address = start_address.saturating_add(length).min(MEMORY_SIZE);
+        let addresses = start_address..end_address;
+
+        let region = if addresses.clone().any(|address| address < PROGRAM_OFFSET) {
+            "the font/interpreter region"
+        } else if addresses
+            .clone()
+            .any(|address| self.executed[address as usize])
+        {
+            "previously executed code"
+        } else {
+            return None;
+        };
+
+        Some(format!(
+            "WARNING: {} at {:#05x} writes {:#05x}-{:#05x} into {}",
+            instruction,
+            program_counter,
+            start_address,
+            end_address - 1,
+            region
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_writes_into_code_and_fonts() {
+        let mut monitor = CodeWriteMonitor::init(CodeWriteAction::Warn);
+        monitor.record_execution(0x200);
+        let instruction = Instructions::DumpRegisters(1);
+
+        let warning = monitor.check_write(0x204, &instruction, 0x201, 2).unwrap();
+        assert_eq!(
+            warning,
+            "WARNING: LD [I], V1 at 0x204 writes 0x201-0x202 into previously executed code"
+        );
+        assert!(monitor
+            .check_write(0x204, &instruction, 0x1ff, 1)
+            .unwrap()
+            .ends_with("into the font/interpreter region"));
+        assert!(monitor.check_write(0x204, &instruction, 0x202, 2).is_none());
+        assert!(!monitor.breaks());
+    }
+
+    #[test]
+    fn clamps_writes_at_the_end_of_the_address_space() {
+        let monitor = CodeWriteMonitor::init(CodeWriteAction::Break);
+        let instruction = Instructions::DumpRegisters(0xf);
+
+        assert!(monitor
+            .check_write(0x200, &instruction, 0xfff8, 16)
+            .is_none());
+        assert!(monitor.breaks());
+    }
+}
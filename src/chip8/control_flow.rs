@@ -0,0 +1,402 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{decode, Address, Instructions, MEMORY_SIZE, PROGRAM_OFFSET};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Call,
+    SkipTaken,
+    SkipNotTaken,
+}
+
+struct BasicBlock {
+    // addresses of all instructions in this block, in order
+    instructions: Vec<Address>,
+    successors: Vec<(Address, EdgeKind)>,
+    has_indirect_jump: bool,
+}
+
+// the control-flow graph of a ROM, found by statically following all jumps, calls and
+// skips reachable from PROGRAM_OFFSET. indirect jumps (BNNN) can not be followed and are
+// only flagged, so code only reachable through them is reported as data.
+pub struct ControlFlowGraph {
+    memory: [u8; MEMORY_SIZE as usize],
+    rom_end: Address,
+    blocks: BTreeMap<Address, BasicBlock>,
+    subroutines: BTreeSet<Address>,
+    reachable_instructions: BTreeSet<Address>,
+}
+
+fn is_control_transfer(instruction: &Instructions) -> bool {
+    matches!(
+        instruction,
+        Instructions::UnconditionalJump(_)
+            | Instructions::UnconditionalJumpWithOffset(_)
+            | Instructions::CallSubroutine(_)
+            | Instructions::ReturnFromSubroutine
+            | Instructions::SkipIfKeyPressedVx(_)
+            | Instructions::SkipIfKeyNotPressedVx(_)
+            | Instructions::SkipIfVxIsIntermediate(_, _)
+            | Instructions::SkipIfVxIsNotIntermediate(_, _)
+            | Instructions::SkipIfVxIsVy(_, _)
+            | Instructions::SkipIfVxIsNotVy(_, _)
+            | Instructions::Unkown
+    )
+}
+
+fn successors(address: Address, instruction: &Instructions) -> Vec<(Address, EdgeKind)> {
+    match *instruction {
+        Instructions::UnconditionalJump(target) => vec![(target, EdgeKind::Jump)],
+        Instructions::CallSubroutine(target) => vec![
+            (target, EdgeKind::Call),
+            (address + 2, EdgeKind::Fallthrough),
+        ],
+        Instructions::SkipIfKeyPressedVx(_)
+        | Instructions::SkipIfKeyNotPressedVx(_)
+        | Instructions::SkipIfVxIsIntermediate(_, _)
+        | Instructions::SkipIfVxIsNotIntermediate(_, _)
+        | Instructions::SkipIfVxIsVy(_, _)
+        | Instructions::SkipIfVxIsNotVy(_, _) => vec![
+            (address + 2, EdgeKind::SkipNotTaken),
+            (address + 4, EdgeKind::SkipTaken),
+        ],
+        Instructions::UnconditionalJumpWithOffset(_)
+        | Instructions::ReturnFromSubroutine
+        | Instructions::Unkown => Vec::new(),
+        _ => vec![(address + 2, EdgeKind::Fallthrough)],
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn extract(rom: &[u8]) -> ControlFlowGraph {
+        let mut memory = [0; MEMORY_SIZE as usize];
+        let rom_length = rom.len().min((MEMORY_SIZE - PROGRAM_OFFSET) as usize);
+        memory[PROGRAM_OFFSET as usize..PROGRAM_OFFSET as usize + rom_length]
+            .copy_from_slice(&rom[..rom_length]);
+
+        let mut graph = ControlFlowGraph {
+            memory,
+            rom_end: PROGRAM_OFFSET + rom_length as Address,
+            blocks: BTreeMap::new(),
+            subroutines: BTreeSet::new(),
+            reachable_instructions: BTreeSet::new(),
+        };
+
+        // first pass: find all reachable instructions and the leaders of the basic blocks
+        let mut leaders = BTreeSet::from([PROGRAM_OFFSET]);
+        let mut worklist = vec![PROGRAM_OFFSET];
+        while let Some(address) = worklist.pop() {
+            if address >= MEMORY_SIZE - 1 || !graph.reachable_instructions.insert(address) {
+                continue;
+            }
+
+            let instruction = graph.instruction_at(address);
+            for (target, kind) in successors(address, &instruction) {
+                if kind == EdgeKind::Call {
+                    graph.subroutines.insert(target);
+                }
+                if is_control_transfer(&instruction) {
+                    leaders.insert(target);
+                }
+                worklist.push(target);
+            }
+        }
+
+        // second pass: split the reachable instructions into basic blocks
+        for &leader in leaders.iter() {
+            if !graph.reachable_instructions.contains(&leader) {
+                continue;
+            }
+
+            let mut instructions = vec![leader];
+            let mut address = leader;
+            loop {
+                let instruction = graph.instruction_at(address);
+                let next_address = address + 2;
+                let ends_block = is_control_transfer(&instruction)
+                    || leaders.contains(&next_address)
+                    || !graph.reachable_instructions.contains(&next_address);
+
+                if ends_block {
+                    graph.blocks.insert(
+                        leader,
+                        BasicBlock {
+                            instructions,
+                            successors: successors(address, &instruction)
+                                .into_iter()
+                                .filter(|(target, _)| *target < MEMORY_SIZE - 1)
+                                .collect(),
+                            has_indirect_jump: matches!(
+                                instruction,
+                                Instructions::UnconditionalJumpWithOffset(_)
+                            ),
+                        },
+                    );
+                    break;
+                }
+
+                address = next_address;
+                instructions.push(address);
+            }
+        }
+
+        graph
+    }
+
+    fn instruction_at(&self, address: Address) -> Instructions {
+        decode(self.raw_instruction_at(address))
+    }
+
+    fn raw_instruction_at(&self, address: Address) -> u16 {
+        ((self.memory[address as usize] as u16) << 8) + self.memory[address as usize + 1] as u16
+    }
+
+    pub fn indirect_jumps(&self) -> Vec<Address> {
+        self.blocks
+            .values()
+            .filter(|block| block.has_indirect_jump)
+            .filter_map(|block| block.instructions.last().copied())
+            .collect()
+    }
+
+    // all ranges (start inclusive, end exclusive) of the ROM which are never reached as
+    // code and are therefore assumed to be data
+    pub fn data_regions(&self) -> Vec<(Address, Address)> {
+        let mut is_code = vec![false; MEMORY_SIZE as usize];
+        for &address in self.reachable_instructions.iter() {
+            is_code[address as usize] = true;
+            is_code[address as usize + 1] = true;
+        }
+
+        let mut regions = Vec::new();
+        let mut region_start = None;
+        for address in PROGRAM_OFFSET..self.rom_end {
+            match (is_code[address as usize], region_start) {
+                (false, None) => region_start = Some(address),
+                (true, Some(start)) => {
+                    regions.push((start, address));
+                    region_start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(start) = region_start {
+            regions.push((start, self.rom_end));
+        }
+
+        regions
+    }
+
+    // the entry of the function a block belongs to: every function entry belongs to itself,
+    // other blocks to the first function (main program first, then subroutines by address)
+    // which reaches them without following calls
+    fn functions(&self) -> BTreeMap<Address, Address> {
+        let entries: Vec<Address> = std::iter::once(PROGRAM_OFFSET)
+            .chain(self.subroutines.iter().copied())
+            .filter(|entry| self.blocks.contains_key(entry))
+            .collect();
+        let mut owners: BTreeMap<Address, Address> =
+            entries.iter().map(|&entry| (entry, entry)).collect();
+
+        for &entry in entries.iter() {
+            let mut visited = BTreeSet::new();
+            let mut worklist = vec![entry];
+            while let Some(leader) = worklist.pop() {
+                if !visited.insert(leader)
+                    || owners.get(&leader).is_some_and(|&owner| owner != entry)
+                {
+                    continue;
+                }
+                owners.insert(leader, entry);
+                worklist.extend(
+                    self.blocks[&leader]
+                        .successors
+                        .iter()
+                        .filter(|(_, kind)| *kind != EdgeKind::Call)
+                        .map(|(target, _)| *target)
+                        .filter(|target| self.blocks.contains_key(target)),
+                );
+            }
+        }
+
+        owners
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let owners = self.functions();
+
+        writeln!(dot, "digraph chip8 {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        let entries = std::iter::once(PROGRAM_OFFSET).chain(self.subroutines.iter().copied());
+        for entry in entries {
+            if !self.blocks.contains_key(&entry) {
+                continue;
+            }
+
+            let name = if entry == PROGRAM_OFFSET {
+                "main".to_string()
+            } else {
+                format!("sub_{:03x}", entry)
+            };
+            writeln!(dot, "    subgraph cluster_{:03x} {{", entry).unwrap();
+            writeln!(dot, "        label=\"{}\";", name).unwrap();
+
+            for (leader, block) in self.blocks.iter() {
+                if owners.get(leader) != Some(&entry) {
+                    continue;
+                }
+
+                let mut label = String::new();
+                for &address in block.instructions.iter() {
+                    write!(
+                        label,
+                        "{:#05x}: {:04x}  {}\\l",
+                        address,
+                        self.raw_instruction_at(address),
+                        self.instruction_at(address)
+                    )
+                    .unwrap();
+                }
+                let style = if block.has_indirect_jump {
+                    ", color=red, xlabel=\"indirect jump\""
+                } else {
+                    ""
+                };
+                writeln!(
+                    dot,
+                    "        block_{:03x} [label=\"{}\"{}];",
+                    leader, label, style
+                )
+                .unwrap();
+            }
+
+            writeln!(dot, "    }}").unwrap();
+        }
+
+        for (leader, block) in self.blocks.iter() {
+            for (target, kind) in block.successors.iter() {
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Call => " [label=\"call\", style=dashed]",
+                    EdgeKind::SkipTaken => " [label=\"skip\", color=darkgreen]",
+                    EdgeKind::SkipNotTaken => " [label=\"no skip\", color=orange]",
+                };
+                writeln!(
+                    dot,
+                    "    block_{:03x} -> block_{:03x}{};",
+                    leader, target, style
+                )
+                .unwrap();
+            }
+        }
+
+        for (start, end) in self.data_regions() {
+            writeln!(dot, "    // data: {:#05x}-{:#05x}", start, end - 1).unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // main calls a subroutine, then skips either onto a jump into an endless loop or onto
+    // an indirect jump. the last two bytes are never reached.
+    const ROM: [u8; 16] = [
+        0x22, 0x08, // 0x200: CALL 0x208
+        0x30, 0x05, // 0x202: SE V0, 0x05
+        0x12, 0x0c, // 0x204: JP 0x20c
+        0xb3, 0x00, // 0x206: JP V0, 0x300
+        0x60, 0x01, // 0x208: LD V0, 0x01
+        0x00, 0xee, // 0x20a: RET
+        0x12, 0x0c, // 0x20c: JP 0x20c
+        0xff, 0xff, // 0x20e: data
+    ];
+
+    fn block(graph: &ControlFlowGraph, leader: Address) -> &BasicBlock {
+        graph
+            .blocks
+            .get(&leader)
+            .unwrap_or_else(|| panic!("no block at {:#05x}", leader))
+    }
+
+    #[test]
+    fn splits_blocks_at_calls_skips_and_jumps() {
+        let graph = ControlFlowGraph::extract(&ROM);
+
+        assert_eq!(
+            graph.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20c]
+        );
+        assert_eq!(
+            block(&graph, 0x200).successors,
+            vec![(0x208, EdgeKind::Call), (0x202, EdgeKind::Fallthrough)]
+        );
+        assert_eq!(
+            block(&graph, 0x202).successors,
+            vec![
+                (0x204, EdgeKind::SkipNotTaken),
+                (0x206, EdgeKind::SkipTaken)
+            ]
+        );
+        assert_eq!(
+            block(&graph, 0x204).successors,
+            vec![(0x20c, EdgeKind::Jump)]
+        );
+        assert_eq!(block(&graph, 0x208).instructions, vec![0x208, 0x20a]);
+        assert!(block(&graph, 0x208).successors.is_empty());
+        assert_eq!(
+            block(&graph, 0x20c).successors,
+            vec![(0x20c, EdgeKind::Jump)]
+        );
+    }
+
+    #[test]
+    fn flags_indirect_jumps_and_data() {
+        let graph = ControlFlowGraph::extract(&ROM);
+
+        assert_eq!(graph.indirect_jumps(), vec![0x206]);
+        assert!(block(&graph, 0x206).successors.is_empty());
+        assert_eq!(graph.data_regions(), vec![(0x20e, 0x210)]);
+    }
+
+    #[test]
+    fn stops_at_zeroed_memory() {
+        // LD V0, 0x01, then zero padding
+        let graph = ControlFlowGraph::extract(&[0x60, 0x01, 0x00, 0x00, 0x00, 0x00]);
+
+        assert_eq!(
+            graph.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![0x200]
+        );
+        assert_eq!(block(&graph, 0x200).instructions, vec![0x200, 0x202]);
+        assert!(block(&graph, 0x200).successors.is_empty());
+        assert_eq!(graph.data_regions(), vec![(0x204, 0x206)]);
+    }
+
+    #[test]
+    fn assigns_blocks_to_functions() {
+        let graph = ControlFlowGraph::extract(&ROM);
+        let owners = graph.functions();
+
+        assert_eq!(graph.subroutines, BTreeSet::from([0x208]));
+        for leader in [0x200, 0x202, 0x204, 0x206, 0x20c] {
+            assert_eq!(owners[&leader], PROGRAM_OFFSET);
+        }
+        assert_eq!(owners[&0x208], 0x208);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("label=\"sub_208\";"));
+        assert!(dot.contains("block_200 -> block_208 [label=\"call\", style=dashed];"));
+        assert!(dot.contains("// data: 0x20e-0x20f"));
+    }
+}
@@ -0,0 +1,284 @@
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+// share of the full deflection from which a stick counts as pushed into a direction
+pub const DEFAULT_AXIS_THRESHOLD: f32 = 0.5;
+
+// a button or a direction of an axis of a game controller
+#[derive(Clone, Copy, PartialEq)]
+pub enum ControllerInput {
+    Button(Button),
+    // the axis pushed into the positive (right, down) or negative (left, up) direction
+    Axis(Axis, bool),
+}
+
+impl ControllerInput {
+    // parses the names used by SDL controller mappings, e.g. "a", "dpup" or "leftshoulder".
+    // axis directions are the axis name followed by + or -, e.g. "leftx-" for left.
+    pub fn from_name(name: &str) -> Option<ControllerInput> {
+        if let Some(axis) = name.strip_suffix('+') {
+            Axis::from_string(axis).map(|axis| ControllerInput::Axis(axis, true))
+        } else if let Some(axis) = name.strip_suffix('-') {
+            Axis::from_string(axis).map(|axis| ControllerInput::Axis(axis, false))
+        } else {
+            Button::from_string(name).map(ControllerInput::Button)
+        }
+    }
+}
+
+// which controller inputs press which CHIP-8 keys. several inputs can press the same key.
+#[derive(Clone)]
+pub struct ControllerMapping {
+    bindings: Vec<(ControllerInput, u8)>,
+    // share of the full deflection, between 0 and 1
+    axis_threshold: f32,
+}
+
+impl ControllerMapping {
+    // replaces the key of the input
+    pub fn bind(&mut self, input: ControllerInput, key: u8) {
+        self.bindings.retain(|(bound, _)| *bound != input);
+        self.bindings.push((input, key));
+    }
+
+    pub fn set_axis_threshold(&mut self, axis_threshold: f32) {
+        self.axis_threshold = axis_threshold.clamp(0.0, 1.0);
+    }
+}
+
+// the D-pad and the left stick press the keys most games use for the directions (2, 4, 6
+// and 8), A presses 5 in their middle, which many games use as action key
+impl Default for ControllerMapping {
+    fn default() -> ControllerMapping {
+        ControllerMapping {
+            bindings: vec![
+                (ControllerInput::Button(Button::DPadUp), 0x2),
+                (ControllerInput::Button(Button::DPadDown), 0x8),
+                (ControllerInput::Button(Button::DPadLeft), 0x4),
+                (ControllerInput::Button(Button::DPadRight), 0x6),
+                (ControllerInput::Axis(Axis::LeftY, false), 0x2),
+                (ControllerInput::Axis(Axis::LeftY, true), 0x8),
+                (ControllerInput::Axis(Axis::LeftX, false), 0x4),
+                (ControllerInput::Axis(Axis::LeftX, true), 0x6),
+                (ControllerInput::Button(Button::A), 0x5),
+                (ControllerInput::Button(Button::B), 0x0),
+                (ControllerInput::Button(Button::X), 0x7),
+                (ControllerInput::Button(Button::Y), 0x9),
+                (ControllerInput::Button(Button::LeftShoulder), 0x1),
+                (ControllerInput::Button(Button::RightShoulder), 0x3),
+                (ControllerInput::Button(Button::Back), 0xe),
+                (ControllerInput::Button(Button::Start), 0xf),
+            ],
+            axis_threshold: DEFAULT_AXIS_THRESHOLD,
+        }
+    }
+}
+
+// the connected game controllers. controllers are opened when SDL reports them, which
+// happens for the ones connected at startup as well as for ones plugged in later.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+    mapping: ControllerMapping,
+    // the held buttons and the positions of the axes, with the id of their controller
+    held_buttons: Vec<(u32, Button)>,
+    axes: Vec<(u32, Axis, i16)>,
+}
+
+impl Controllers {
+    pub fn init(sdl_context: &sdl2::Sdl) -> Controllers {
+        // the emulator still works with the keyboard if there is no controller support
+        let subsystem = match sdl_context.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(err) => {
+                eprintln!("ERROR: Could not initialize game controllers: {}", err);
+                None
+            }
+        };
+
+        Controllers {
+            subsystem,
+            controllers: Vec::new(),
+            mapping: ControllerMapping::default(),
+            held_buttons: Vec::new(),
+            axes: Vec::new(),
+        }
+    }
+
+    pub fn set_mapping(&mut self, mapping: ControllerMapping) {
+        self.mapping = mapping;
+    }
+
+    fn is_active(&self, input: ControllerInput) -> bool {
+        match input {
+            ControllerInput::Button(button) => {
+                self.held_buttons.iter().any(|&(_, held)| held == button)
+            }
+            ControllerInput::Axis(axis, positive) => {
+                let threshold = self.mapping.axis_threshold * i16::MAX as f32;
+                self.axes.iter().any(|&(_, moved, value)| {
+                    let value = if positive {
+                        value as i32
+                    } else {
+                        -(value as i32)
+                    };
+                    moved == axis && value as f32 > threshold
+                })
+            }
+        }
+    }
+
+    pub fn key_states(&self) -> [bool; 16] {
+        let mut key_states = [false; 16];
+        for &(input, key) in self.mapping.bindings.iter() {
+            if self.is_active(input) {
+                key_states[key as usize] = true;
+            }
+        }
+        key_states
+    }
+
+    // returns false if the event is not a controller event
+    pub fn process_event(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let Some(subsystem) = &self.subsystem else {
+                    return true;
+                };
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.controllers.push(controller);
+                    }
+                    Err(err) => eprintln!("ERROR: Could not open controller: {}", err),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(index) = self
+                    .controllers
+                    .iter()
+                    .position(|controller| controller.instance_id() == which)
+                {
+                    println!(
+                        "Controller disconnected: {}",
+                        self.controllers[index].name()
+                    );
+                    self.controllers.remove(index);
+                }
+                // the inputs of the removed controller are released
+                self.held_buttons.retain(|&(id, _)| id != which);
+                self.axes.retain(|&(id, _, _)| id != which);
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                if !self.held_buttons.contains(&(which, button)) {
+                    self.held_buttons.push((which, button));
+                }
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.held_buttons.retain(|&held| held != (which, button));
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                self.axes
+                    .retain(|&(id, moved, _)| (id, moved) != (which, axis));
+                self.axes.push((which, axis, value));
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::sys;
+
+    // the mapping of the virtual controller: 15 buttons and 6 axes in the order of SDL
+    const MAPPING: &str = "a:b0,b:b1,x:b2,y:b3,back:b4,guide:b5,start:b6,leftstick:b7,\
+        rightstick:b8,leftshoulder:b9,rightshoulder:b10,dpup:b11,dpdown:b12,dpleft:b13,\
+        dpright:b14,leftx:a0,lefty:a1,rightx:a2,righty:a3,lefttrigger:a4,righttrigger:a5";
+
+    fn pump(event_pump: &mut sdl2::EventPump, controllers: &mut Controllers) {
+        for event in event_pump.poll_iter() {
+            controllers.process_event(&event);
+        }
+    }
+
+    #[test]
+    fn maps_a_virtual_controller_to_keys() {
+        let sdl_context = sdl2::init().unwrap();
+        let joystick_subsystem = sdl_context.joystick().unwrap();
+        let mut event_pump = sdl_context.event_pump().unwrap();
+        let mut controllers = Controllers::init(&sdl_context);
+
+        // SAFETY: the joystick subsystem is initialized
+        let index = unsafe {
+            sys::SDL_JoystickAttachVirtual(
+                sys::SDL_JoystickType::SDL_JOYSTICK_TYPE_GAMECONTROLLER,
+                6,
+                15,
+                0,
+            )
+        };
+        assert!(index >= 0, "{}", sdl2::get_error());
+        let guid = joystick_subsystem.device_guid(index as u32).unwrap();
+        controllers
+            .subsystem
+            .as_ref()
+            .unwrap()
+            .add_mapping(&format!("{},Virtual controller,{}", guid.string(), MAPPING))
+            .unwrap();
+        // SAFETY: index is the device index of the attached virtual joystick
+        let joystick = unsafe { sys::SDL_JoystickOpen(index) };
+        assert!(!joystick.is_null(), "{}", sdl2::get_error());
+        pump(&mut event_pump, &mut controllers);
+        assert_eq!(controllers.controllers.len(), 1);
+
+        let set_button = |button: i32, pressed: bool| {
+            // SAFETY: joystick is open until it is closed below
+            unsafe { sys::SDL_JoystickSetVirtualButton(joystick, button, pressed as u8) };
+        };
+        let set_axis = |axis: i32, value: i16| {
+            // SAFETY: joystick is open until it is closed below
+            unsafe { sys::SDL_JoystickSetVirtualAxis(joystick, axis, value) };
+        };
+
+        // A and dpdown
+        set_button(0, true);
+        set_button(12, true);
+        pump(&mut event_pump, &mut controllers);
+        let key_states = controllers.key_states();
+        assert!(key_states[0x5] && key_states[0x8]);
+        assert_eq!(key_states.iter().filter(|&&pressed| pressed).count(), 2);
+
+        set_button(0, false);
+        pump(&mut event_pump, &mut controllers);
+        assert!(!controllers.key_states()[0x5]);
+
+        // the left stick only counts from half of the full deflection
+        set_axis(0, 16000);
+        set_axis(1, -16000);
+        pump(&mut event_pump, &mut controllers);
+        let key_states = controllers.key_states();
+        assert!(!key_states[0x6] && !key_states[0x2]);
+
+        set_axis(0, 17000);
+        set_axis(1, -17000);
+        pump(&mut event_pump, &mut controllers);
+        let key_states = controllers.key_states();
+        assert!(key_states[0x6] && key_states[0x2] && !key_states[0x4]);
+
+        // unplugging releases everything the controller held
+        // SAFETY: the joystick is closed once and the device index is still valid
+        unsafe {
+            sys::SDL_JoystickClose(joystick);
+            sys::SDL_JoystickDetachVirtual(index);
+        }
+        pump(&mut event_pump, &mut controllers);
+        assert!(controllers.controllers.is_empty());
+        assert_eq!(controllers.key_states(), [false; 16]);
+    }
+}
@@ -0,0 +1,285 @@
+// post-processing of the scaled output image, done on the CPU so it works without a GPU.
+// images are RGB with 3 bytes per pixel, row by row without padding.
+
+const SCANLINE_BRIGHTNESS: f32 = 0.6;
+const GRID_BRIGHTNESS: f32 = 0.35;
+const BLOOM_STRENGTH: f32 = 0.45;
+// how strongly the corners are pulled outwards by the curvature
+const CURVATURE: f32 = 0.06;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CrtFilter {
+    Scanlines,
+    Grid,
+    Bloom,
+    Curvature,
+}
+
+pub const FILTER_NAMES: [&str; 4] = ["scanlines", "grid", "bloom", "curvature"];
+
+impl CrtFilter {
+    pub fn from_name(name: &str) -> Option<CrtFilter> {
+        match name.trim() {
+            "scanlines" => Some(CrtFilter::Scanlines),
+            "grid" => Some(CrtFilter::Grid),
+            "bloom" => Some(CrtFilter::Bloom),
+            "curvature" => Some(CrtFilter::Curvature),
+            _ => None,
+        }
+    }
+}
+
+// the filters are always applied in the same order, the one of the enum
+pub fn apply_filters(
+    filters: &[CrtFilter],
+    image: &mut [u8],
+    width: usize,
+    height: usize,
+    scale: usize,
+) {
+    for filter in [
+        CrtFilter::Scanlines,
+        CrtFilter::Grid,
+        CrtFilter::Bloom,
+        CrtFilter::Curvature,
+    ] {
+        if !filters.contains(&filter) {
+            continue;
+        }
+
+        match filter {
+            CrtFilter::Scanlines => scanlines(image, width, height, scale),
+            CrtFilter::Grid => grid(image, width, height, scale),
+            CrtFilter::Bloom => bloom(image, width, height, scale),
+            CrtFilter::Curvature => curvature(image, width, height),
+        }
+    }
+}
+
+// nearest-neighbour scaling by a whole number
+pub fn scale_image(image: &[u8], width: usize, height: usize, scale: usize) -> Vec<u8> {
+    let mut scaled = vec![0; width * scale * height * scale * 3];
+
+    for y in 0..height * scale {
+        for x in 0..width * scale {
+            let source = ((y / scale) * width + x / scale) * 3;
+            let target = (y * width * scale + x) * 3;
+            scaled[target..target + 3].copy_from_slice(&image[source..source + 3]);
+        }
+    }
+
+    scaled
+}
+
+fn darken(pixel: &mut [u8], brightness: f32) {
+    for channel in pixel.iter_mut() {
+        *channel = (*channel as f32 * brightness) as u8;
+    }
+}
+
+// darkens the lower half of every scaled pixel row (every other line without scaling)
+fn scanlines(image: &mut [u8], width: usize, height: usize, scale: usize) {
+    for y in 0..height {
+        let is_gap = if scale < 2 {
+            y % 2 == 1
+        } else {
+            y % scale >= scale.div_ceil(2)
+        };
+        if is_gap {
+            darken(
+                &mut image[y * width * 3..(y + 1) * width * 3],
+                SCANLINE_BRIGHTNESS,
+            );
+        }
+    }
+}
+
+// darkens the last row and column of every scaled pixel, so gaps between the pixels appear
+fn grid(image: &mut [u8], width: usize, height: usize, scale: usize) {
+    if scale < 3 {
+        return;
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            if y % scale == scale - 1 || x % scale == scale - 1 {
+                let offset = (y * width + x) * 3;
+                darken(&mut image[offset..offset + 3], GRID_BRIGHTNESS);
+            }
+        }
+    }
+}
+
+// one pass of a box blur along rows (step 3) or columns (step width * 3)
+fn box_blur(
+    image: &[u8],
+    lines: usize,
+    length: usize,
+    line_step: usize,
+    step: usize,
+    radius: usize,
+) -> Vec<u8> {
+    let mut blurred = vec![0; image.len()];
+
+    for line in 0..lines {
+        for channel in 0..3 {
+            let at = |i: usize| image[line * line_step + i * step + channel] as u32;
+            let mut sum: u32 = (0..=radius.min(length - 1)).map(at).sum();
+
+            for i in 0..length {
+                let first = i.saturating_sub(radius);
+                let last = (i + radius).min(length - 1);
+                blurred[line * line_step + i * step + channel] =
+                    (sum / (last - first + 1) as u32) as u8;
+
+                // slide the window one pixel further
+                if i + radius + 1 < length {
+                    sum += at(i + radius + 1);
+                }
+                if i >= radius {
+                    sum -= at(i - radius);
+                }
+            }
+        }
+    }
+
+    blurred
+}
+
+// adds a blurred copy of the image, so lit pixels glow into their surroundings
+fn bloom(image: &mut [u8], width: usize, height: usize, scale: usize) {
+    let radius = scale.max(2);
+    let blurred = box_blur(image, height, width, width * 3, 3, radius);
+    let blurred = box_blur(&blurred, width, height, 3, width * 3, radius);
+
+    for (channel, glow) in image.iter_mut().zip(blurred.iter()) {
+        *channel = (*channel as f32 + *glow as f32 * BLOOM_STRENGTH).min(255.0) as u8;
+    }
+}
+
+// barrel distortion like the bulged glass of a CRT. the corners end up outside the image
+// and are black.
+fn curvature(image: &mut [u8], width: usize, height: usize) {
+    let source = image.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            // coordinates from -1.0 to 1.0 relative to the center
+            let u = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+            let distortion = 1.0 + CURVATURE * (u * u + v * v);
+            let source_x = ((u * distortion + 1.0) / 2.0 * width as f32).floor();
+            let source_y = ((v * distortion + 1.0) / 2.0 * height as f32).floor();
+
+            let offset = (y * width + x) * 3;
+            if source_x < 0.0
+                || source_y < 0.0
+                || source_x >= width as f32
+                || source_y >= height as f32
+            {
+                image[offset..offset + 3].fill(0);
+            } else {
+                let source_offset = (source_y as usize * width + source_x as usize) * 3;
+                image[offset..offset + 3]
+                    .copy_from_slice(&source[source_offset..source_offset + 3]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    fn image(pixels: &[[u8; 3]]) -> Vec<u8> {
+        pixels.concat()
+    }
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> [u8; 3] {
+        let offset = (y * width + x) * 3;
+        image[offset..offset + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn scales_by_whole_numbers() {
+        let scaled = scale_image(&image(&[RED, BLUE]), 2, 1, 2);
+
+        assert_eq!(scaled, image(&[RED, RED, BLUE, BLUE, RED, RED, BLUE, BLUE]));
+        assert_eq!(
+            scale_image(&image(&[RED, BLUE]), 2, 1, 1),
+            image(&[RED, BLUE])
+        );
+    }
+
+    #[test]
+    fn darkens_scanlines_and_grid() {
+        let white = [255; 3];
+        let dark_line = [(255.0 * SCANLINE_BRIGHTNESS) as u8; 3];
+        let dark_grid = [(255.0 * GRID_BRIGHTNESS) as u8; 3];
+
+        for (scale, dark_rows) in [(1, vec![1, 3, 5]), (2, vec![1, 3, 5]), (3, vec![2, 5])] {
+            let mut column = image(&[white; 6]);
+            scanlines(&mut column, 1, 6, scale);
+            for y in 0..6 {
+                let expected = if dark_rows.contains(&y) {
+                    dark_line
+                } else {
+                    white
+                };
+                assert_eq!(pixel(&column, 1, 0, y), expected, "scale {}", scale);
+            }
+        }
+
+        let mut square = image(&[white; 9]);
+        grid(&mut square, 3, 3, 3);
+        assert_eq!(pixel(&square, 3, 1, 1), white);
+        assert_eq!(pixel(&square, 3, 2, 0), dark_grid);
+        assert_eq!(pixel(&square, 3, 0, 2), dark_grid);
+        assert_eq!(pixel(&square, 3, 2, 2), dark_grid);
+
+        // smaller pixels have no room for gaps
+        let mut square = image(&[white; 4]);
+        grid(&mut square, 2, 2, 2);
+        assert_eq!(square, image(&[white; 4]));
+    }
+
+    #[test]
+    fn blurs_within_the_image() {
+        // a single line, the window is cut off at both ends
+        let line = image(&[[0; 3], [0; 3], [90; 3], [0; 3], [0; 3]]);
+        let blurred = box_blur(&line, 1, 5, 15, 3, 1);
+        assert_eq!(blurred, image(&[[0; 3], [30; 3], [30; 3], [30; 3], [0; 3]]));
+
+        // a radius beyond the image averages all pixels
+        let line = image(&[[30; 3], [60; 3], [90; 3]]);
+        assert_eq!(box_blur(&line, 1, 3, 9, 3, 5), image(&[[60; 3]; 3]));
+
+        // columns of a 2x2 image
+        let square = image(&[[40; 3], [0; 3], [0; 3], [0; 3]]);
+        let blurred = box_blur(&square, 2, 2, 3, 6, 1);
+        assert_eq!(blurred, image(&[[20; 3], [0; 3], [20; 3], [0; 3]]));
+
+        // a 1x1 image only glows onto itself
+        let mut dot = image(&[[100; 3]]);
+        bloom(&mut dot, 1, 1, 1);
+        assert_eq!(dot, image(&[[145; 3]]));
+    }
+
+    #[test]
+    fn bends_the_image_at_the_corners() {
+        let size = 20;
+        let mut gradient: Vec<u8> = (0..size * size)
+            .flat_map(|i| [(i % size) as u8, (i / size) as u8, 255])
+            .collect();
+        curvature(&mut gradient, size, size);
+
+        // the center stays in place, the corners come from outside the image
+        assert_eq!(pixel(&gradient, size, 10, 10), [10, 10, 255]);
+        for (x, y) in [(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1)] {
+            assert_eq!(pixel(&gradient, size, x, y), [0; 3]);
+        }
+    }
+}
@@ -1,52 +1,57 @@
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-
-const DISPLAY_SCALE_FACTOR: u32 = 10;
-
-const DISPLAY_WIDTH: u32 = 64;
-const DISPLAY_HEIGHT: u32 = 32;
-
-// TODO: add flag to indicate change in disp_buffer: only draw if there was a change
+pub const DISPLAY_WIDTH: u32 = 64;
+pub const DISPLAY_HEIGHT: u32 = 32;
+
+// returns whether the pixel at (x, y) of the sprite starting at start_adress is set.
+// sprites are stored row by row, bytes_per_row bytes each, most significant bit first.
+pub fn sprite_pixel(
+    memory: &[u8],
+    start_adress: usize,
+    bytes_per_row: usize,
+    x: usize,
+    y: usize,
+) -> bool {
+    memory
+        .get(start_adress + y * bytes_per_row + x / 8)
+        .is_some_and(|row| row & (128 >> (x % 8)) != 0)
+}
 
+// the display buffer of the CHIP-8. showing it is up to the frontend.
 pub struct Display {
-    canvas: sdl2::render::Canvas<sdl2::video::Window>,
     disp_buffer: [bool; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize],
+    // set whenever disp_buffer changes, so frontends can skip unchanged frames
+    is_dirty: bool,
 }
 
 impl Display {
-    pub fn init(sdl_context: sdl2::Sdl) -> Display {
-        let video_subsystem = sdl_context
-            .video()
-            .expect("ERROR: Could not initialize video-subsystem. Exiting...");
+    pub fn init() -> Display {
+        Display {
+            disp_buffer: [false; (DISPLAY_HEIGHT * DISPLAY_WIDTH) as usize],
+            is_dirty: true,
+        }
+    }
 
-        let window = video_subsystem
-            .window(
-                "rust-sdl2 demo",
-                DISPLAY_WIDTH * DISPLAY_SCALE_FACTOR,
-                DISPLAY_HEIGHT * DISPLAY_SCALE_FACTOR,
-            )
-            .position_centered()
-            .build()
-            .expect("ERROR: Unable to initialize SDL2 video-subsystem. Exiting...");
+    pub fn buffer(&self) -> &[bool] {
+        &self.disp_buffer
+    }
 
-        let mut canvas = window
-            .into_canvas()
-            .accelerated()
-            .build()
-            .expect("ERROR: Unable to create canvas in SDL2-window. Exiting...");
+    pub fn set_buffer(&mut self, buffer: &[bool]) {
+        self.disp_buffer.copy_from_slice(buffer);
+        self.is_dirty = true;
+    }
 
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        canvas.present();
+    // forces the next frame to be shown again, e.g. after the colors changed
+    pub fn mark_dirty(&mut self) {
+        self.is_dirty = true;
+    }
 
-        Display {
-            canvas: canvas,
-            disp_buffer: [false; (DISPLAY_HEIGHT * DISPLAY_WIDTH) as usize],
-        }
+    // returns whether the buffer changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.is_dirty, false)
     }
 
     pub fn clear_screen(&mut self) {
-        self.disp_buffer.map(|_| false);
+        self.disp_buffer = [false; (DISPLAY_HEIGHT * DISPLAY_WIDTH) as usize];
+        self.is_dirty = true;
     }
 
     pub fn blend_sprite(
@@ -64,13 +69,14 @@ impl Display {
         let start_adress = start_adress as usize;
 
         let mut was_turned_off = false;
+        self.is_dirty = true;
 
         for y in 0..height as usize {
             for x in 0..8usize {
                 let actual_x = (x + x_coord) % DISPLAY_WIDTH as usize;
                 let actual_y = (y + y_coord) % DISPLAY_HEIGHT as usize;
                 let result = self.disp_buffer[actual_y * DISPLAY_WIDTH as usize + actual_x]
-                    ^ (memory[start_adress + y] & (128 >> x) != 0);
+                    ^ sprite_pixel(memory, start_adress, 1, x, y);
                 self.disp_buffer[actual_y * DISPLAY_WIDTH as usize + actual_x] = result;
                 if !result {
                     was_turned_off = true;
@@ -80,34 +86,4 @@ impl Display {
 
         was_turned_off
     }
-
-    pub fn draw(&mut self) {
-        self.canvas.set_draw_color(Color::BLACK);
-        self.canvas.clear();
-        self.canvas.set_draw_color(Color::WHITE);
-
-        // TODO: calling draw_rect for every white is a waste. use draw texture or something...
-        for y in 0..DISPLAY_HEIGHT {
-            for x in 0..DISPLAY_WIDTH {
-                if self.disp_buffer[(y * DISPLAY_WIDTH + x) as usize] {
-                    let rect = Rect::new(
-                        (x * DISPLAY_SCALE_FACTOR) as i32,
-                        (y * DISPLAY_SCALE_FACTOR) as i32,
-                        DISPLAY_SCALE_FACTOR,
-                        DISPLAY_SCALE_FACTOR,
-                    );
-
-                    self.canvas
-                        .fill_rect(rect)
-                        .expect("ERROR: Could not fill rectangle");
-
-                    self.canvas
-                        .draw_rect(rect)
-                        .expect("ERROR: Could not draw pixel. Exiting...");
-                }
-            }
-        }
-
-        self.canvas.present();
-    }
 }
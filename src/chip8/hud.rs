@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+const MAX_MESSAGES: usize = 4;
+const FRAMES_PER_SECOND: f64 = 60.0;
+
+// the bundled 3x5 font, one byte per row with the 3 lowest bits set for lit pixels.
+// letters are only available in upper case, unknown characters are shown as '?'.
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+// returns whether the pixel at (x, y) of the glyph of the character is lit
+pub fn glyph_pixel(character: char, x: u32, y: u32) -> bool {
+    glyph(character)[y as usize] & (0b100 >> x) != 0
+}
+
+// the on-screen display: statistics of the emulation (if enabled), whether it is paused
+// and messages shown for a few seconds
+pub struct Hud {
+    show_statistics: bool,
+    messages: VecDeque<(String, Instant)>,
+    // measurement over the current second
+    second_start: Instant,
+    first_cycle: u64,
+    frames: u32,
+    draws: u32,
+    // results of the last complete second
+    instructions_per_second: u64,
+    frames_per_second: u32,
+    speed: f64,
+}
+
+impl Hud {
+    pub fn init() -> Hud {
+        Hud {
+            show_statistics: false,
+            messages: VecDeque::new(),
+            second_start: Instant::now(),
+            first_cycle: 0,
+            frames: 0,
+            draws: 0,
+            instructions_per_second: 0,
+            frames_per_second: 0,
+            speed: 0.0,
+        }
+    }
+
+    pub fn set_show_statistics(&mut self, show_statistics: bool) {
+        self.show_statistics = show_statistics;
+    }
+
+    pub fn toggle_statistics(&mut self) {
+        self.show_statistics = !self.show_statistics;
+    }
+
+    pub fn show_message(&mut self, message: String) {
+        self.messages.push_back((message, Instant::now()));
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn count_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    // called once per drawn frame with the number of cycles executed so far
+    pub fn count_draw(&mut self, cycle: u64) {
+        self.draws += 1;
+
+        let elapsed = self.second_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let seconds = elapsed.as_secs_f64();
+            self.instructions_per_second =
+                (cycle.saturating_sub(self.first_cycle) as f64 / seconds).round() as u64;
+            self.frames_per_second = (self.draws as f64 / seconds).round() as u32;
+            // the speed is measured in emulated frames, which are 1/60s each
+            self.speed = self.frames as f64 / seconds / FRAMES_PER_SECOND * 100.0;
+
+            self.second_start = Instant::now();
+            self.first_cycle = cycle;
+            self.frames = 0;
+            self.draws = 0;
+        }
+
+        self.messages
+            .retain(|(_, shown_at)| shown_at.elapsed() < MESSAGE_DURATION);
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn instructions_per_second(&self) -> u64 {
+        self.instructions_per_second
+    }
+
+    // the lines shown in the upper left corner
+    pub fn status_lines(&self, paused: bool, polled_keys: &[bool; 16]) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.show_statistics {
+            lines.push(format!(
+                "IPS: {}  FPS: {}  SPEED: {:.0}%",
+                self.instructions_per_second, self.frames_per_second, self.speed
+            ));
+            let keys: Vec<String> = (0..16)
+                .filter(|&key| polled_keys[key])
+                .map(|key| format!("{:X}", key))
+                .collect();
+            lines.push(format!("POLLED KEYS: {}", keys.join(" ")));
+        }
+        if paused {
+            lines.push("PAUSED".to_string());
+        }
+        lines
+    }
+
+    // the lines shown in the lower left corner, the newest last
+    pub fn message_lines(&self) -> Vec<String> {
+        self.messages
+            .iter()
+            .map(|(message, _)| message.clone())
+            .collect()
+    }
+}
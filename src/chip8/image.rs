@@ -0,0 +1,19 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+// writes an 8-bit RGB image (3 bytes per pixel, row by row) as PNG-file to the given path
+pub fn write_png(
+    path: &str,
+    width: u32,
+    height: u32,
+    rgb: &[u8],
+) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgb)
+}
@@ -1,97 +1,209 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::mouse::MouseButton;
 use sdl2::EventPump;
 
+use super::controller::{ControllerMapping, Controllers};
+use super::keymap::Keymap;
+use super::keypad_panel::KeypadArea;
+
+// the id of mouse events SDL synthesizes from touches, which are handled as touches
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+// the mouse or a finger on the on-screen keypad
+#[derive(Clone, Copy, PartialEq)]
+enum Pointer {
+    Mouse,
+    Finger(i64),
+}
+
 pub struct Input {
     event_pump: EventPump,
+    window_id: u32,
     should_exit: bool,
-    key_states: [bool; 16],
-}
-
-fn convert_keycode_to_u8(keycode: Keycode) -> Option<u8> {
-    match keycode {
-        Keycode::Num1 => Some(0x0),
-        Keycode::Num2 => Some(0x1),
-        Keycode::Num3 => Some(0x2),
-        Keycode::Num4 => Some(0x3),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0x7),
-        Keycode::A => Some(0x8),
-        Keycode::S => Some(0x9),
-        Keycode::D => Some(0xa),
-        Keycode::F => Some(0xb),
-        Keycode::Y => Some(0xc),
-        Keycode::X => Some(0xd),
-        Keycode::C => Some(0xe),
-        Keycode::V => Some(0xf),
-        _ => None,
-    }
+    keymap: Keymap,
+    // the held host keys (by their physical key) with the CHIP-8 keys they are mapped to
+    held_keys: Vec<(Scancode, Vec<u8>)>,
+    controllers: Controllers,
+    // the keys of the on-screen keypad held by the mouse or fingers
+    pointer_keys: Vec<(Pointer, u8)>,
 }
 
 impl Input {
-    pub fn init(sdl_context: sdl2::Sdl) -> Input {
+    pub fn init(sdl_context: sdl2::Sdl, window_id: u32) -> Input {
+        let controllers = Controllers::init(&sdl_context);
         Input {
             event_pump: sdl_context
                 .event_pump()
                 .expect("ERROR: Could not extract event-pump from sdl-context. Exiting..."),
+            window_id,
             should_exit: false,
-            key_states: [false; 16],
+            keymap: Keymap::default(),
+            held_keys: Vec::new(),
+            controllers,
+            pointer_keys: Vec::new(),
         }
     }
 
-    pub fn is_key_pressed(&self, key_code: u8) -> bool {
-        self.key_states[key_code as usize]
+    pub fn set_controller_mapping(&mut self, mapping: ControllerMapping) {
+        self.controllers.set_mapping(mapping);
     }
 
-    pub fn get_key_blocking(&mut self) -> u8 {
-        loop {
-            for event in self.event_pump.poll_iter() {
-                if let Event::KeyDown { keycode, .. } = event {
-                    if let Some(v) = keycode {
-                        if let Some(v) = convert_keycode_to_u8(v) {
-                            return v;
-                        }
-                    }
-                    // TODO handle Event::Quit here!
-                }
-            }
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+        self.held_keys.clear();
+    }
 
-            std::thread::sleep(std::time::Duration::new(0, 20_000));
+    // a CHIP-8 key is pressed while any of its host keys or controller inputs is held
+    pub fn key_states(&self) -> [bool; 16] {
+        let mut key_states = self.controllers.key_states();
+        for (_, keys) in self.held_keys.iter() {
+            for &key in keys {
+                key_states[key as usize] = true;
+            }
+        }
+        for &(_, key) in self.pointer_keys.iter() {
+            key_states[key as usize] = true;
         }
+        key_states
     }
 
     pub fn should_exit(&self) -> bool {
         self.should_exit
     }
 
-    pub fn process_all_events(&mut self) {
-        for event in self.event_pump.poll_iter() {
+    // the pointer holds the key under it. moved off the keys, it holds none.
+    fn press_pointer(&mut self, pointer: Pointer, key: Option<u8>) {
+        self.release_pointer(pointer);
+        if let Some(key) = key {
+            self.pointer_keys.push((pointer, key));
+        }
+    }
+
+    fn release_pointer(&mut self, pointer: Pointer) {
+        self.pointer_keys.retain(|&(held_by, _)| held_by != pointer);
+    }
+
+    fn is_pointer_down(&self, pointer: Pointer) -> bool {
+        self.pointer_keys
+            .iter()
+            .any(|&(held_by, _)| held_by == pointer)
+    }
+
+    // handles clicks and touches of the on-screen keypad, returns false for other events
+    fn process_pointer_event(&mut self, event: &Event, keypad_area: &KeypadArea) -> bool {
+        match *event {
+            Event::MouseButtonDown {
+                which,
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } if which != TOUCH_MOUSE_ID => match keypad_area.key_at(x, y) {
+                Some(key) => self.press_pointer(Pointer::Mouse, Some(key)),
+                None => return false,
+            },
+            Event::MouseMotion {
+                which,
+                mousestate,
+                x,
+                y,
+                ..
+            } if which != TOUCH_MOUSE_ID
+                && mousestate.left()
+                && self.is_pointer_down(Pointer::Mouse) =>
+            {
+                self.press_pointer(Pointer::Mouse, keypad_area.key_at(x, y))
+            }
+            Event::MouseButtonUp {
+                which,
+                mouse_btn: MouseButton::Left,
+                ..
+            } if which != TOUCH_MOUSE_ID && self.is_pointer_down(Pointer::Mouse) => {
+                self.release_pointer(Pointer::Mouse)
+            }
+            Event::FingerDown {
+                finger_id, x, y, ..
+            } => self.press_pointer(
+                Pointer::Finger(finger_id),
+                keypad_area.key_at_relative(x, y),
+            ),
+            Event::FingerMotion {
+                finger_id, x, y, ..
+            } if self.is_pointer_down(Pointer::Finger(finger_id)) => self.press_pointer(
+                Pointer::Finger(finger_id),
+                keypad_area.key_at_relative(x, y),
+            ),
+            Event::FingerUp { finger_id, .. } => self.release_pointer(Pointer::Finger(finger_id)),
+            _ => return false,
+        }
+        true
+    }
+
+    // handles quitting and the CHIP-8 keys of the main window. every other event
+    // (e.g. of additional windows like the sprite-viewer) is returned to the caller.
+    // keypad_area is where the on-screen keypad is, if it is shown
+    pub fn process_all_events(&mut self, keypad_area: Option<KeypadArea>) -> Vec<Event> {
+        let mut unhandled_events = Vec::new();
+
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            if self.controllers.process_event(&event) {
+                continue;
+            }
+            if event.get_window_id().is_some_and(|id| id != self.window_id) {
+                unhandled_events.push(event);
+                continue;
+            }
+            if let Some(keypad_area) = &keypad_area {
+                if self.process_pointer_event(&event, keypad_area) {
+                    continue;
+                }
+            }
+
             match event {
                 Event::Quit { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => {
                     self.should_exit = true;
                 }
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(v) = keycode {
-                        if let Some(v) = convert_keycode_to_u8(v) {
-                            self.key_states[v as usize] = true;
-                        }
+                Event::KeyDown {
+                    keycode,
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    let keys = self.keymap.chip8_keys(keycode, Some(scancode));
+                    if keys.is_empty() {
+                        unhandled_events.push(event);
+                    } else if !self.held_keys.iter().any(|(held, _)| *held == scancode) {
+                        self.held_keys.push((scancode, keys));
                     }
                 }
-                Event::KeyUp { keycode, .. } => {
-                    if let Some(v) = keycode {
-                        if let Some(v) = convert_keycode_to_u8(v) {
-                            self.key_states[v as usize] = false;
-                        }
+                // the keys are released by the physical key, as the keycode could change
+                // in between (e.g. when a modifier is released first)
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => match self
+                    .held_keys
+                    .iter()
+                    .position(|(held, _)| *held == scancode)
+                {
+                    Some(index) => {
+                        self.held_keys.remove(index);
                     }
-                }
-                _ => (),
+                    None => unhandled_events.push(event),
+                },
+                _ => unhandled_events.push(event),
             }
         }
+
+        unhandled_events
     }
 }
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+// when an injected key press or release happens: at the start of an emulated frame (counted
+// from 0) or before the given number of cycles has been emulated. cycles halted at FX0A
+// count as well, so a key can be pressed and released while the ROM waits for it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputTime {
+    Frame(u64),
+    Cycle(u64),
+}
+
+#[derive(Clone, Copy)]
+pub struct ScheduledInput {
+    pub time: InputTime,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+fn parse_statement(statement: &str) -> Result<ScheduledInput, String> {
+    let (time, action) = statement
+        .split_once(':')
+        .ok_or("expected \"<frame|cycle> <number>: <press|release> <key>\"")?;
+
+    let time = match time.split_whitespace().collect::<Vec<_>>()[..] {
+        [unit, number] => {
+            let number = number
+                .parse::<u64>()
+                .map_err(|_| format!("invalid number {}", number))?;
+            match unit {
+                "frame" => InputTime::Frame(number),
+                "cycle" => InputTime::Cycle(number),
+                _ => return Err(format!("unknown unit {} (expected frame or cycle)", unit)),
+            }
+        }
+        _ => return Err(format!("invalid time {}", time.trim())),
+    };
+
+    let (pressed, key) = match action.split_whitespace().collect::<Vec<_>>()[..] {
+        ["press", key] => (true, key),
+        ["release", key] => (false, key),
+        _ => return Err(format!("invalid action {}", action.trim())),
+    };
+    let key = match u8::from_str_radix(key, 16) {
+        Ok(key) if key < 16 => key,
+        _ => return Err(format!("invalid key {} (expected 0 to F)", key)),
+    };
+
+    Ok(ScheduledInput { time, key, pressed })
+}
+
+// parses statements like "frame 120: press 5" or "cycle 5000: release A", separated by
+// semicolons or new lines. # starts a comment.
+pub fn parse_input_script(script: &str) -> Result<Vec<ScheduledInput>, String> {
+    let mut inputs = Vec::new();
+
+    for (line_number, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        for statement in line
+            .split(';')
+            .filter(|statement| !statement.trim().is_empty())
+        {
+            let input = parse_statement(statement)
+                .map_err(|err| format!("line {}: {}", line_number + 1, err))?;
+            inputs.push(input);
+        }
+    }
+
+    Ok(inputs)
+}
+
+pub fn read_input_script(path: &Path) -> Result<Vec<ScheduledInput>, String> {
+    let script = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    parse_input_script(&script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(inputs: &[ScheduledInput]) -> Vec<(InputTime, u8, bool)> {
+        inputs
+            .iter()
+            .map(|input| (input.time, input.key, input.pressed))
+            .collect()
+    }
+
+    #[test]
+    fn parses_statements_and_comments() {
+        let script = "# a bot\n\
+            frame 120: press 5; frame 130: release 5 # walk\n\
+            \n\
+            cycle 50000 :  press a;;\n";
+        let inputs = parse_input_script(script).unwrap();
+
+        assert_eq!(
+            summary(&inputs),
+            vec![
+                (InputTime::Frame(120), 0x5, true),
+                (InputTime::Frame(130), 0x5, false),
+                (InputTime::Cycle(50000), 0xa, true),
+            ]
+        );
+        assert!(parse_input_script("# nothing to do\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_statements() {
+        let errors = [
+            (
+                "frame 1: press 10",
+                "line 1: invalid key 10 (expected 0 to F)",
+            ),
+            (
+                "frame 1: press 1\nsecond 2: press 1",
+                "line 2: unknown unit second (expected frame or cycle)",
+            ),
+            ("frame 1 press 1", "line 1: expected"),
+            ("frame x: press 1", "line 1: invalid number x"),
+            ("frame 1: hold 1", "line 1: invalid action hold 1"),
+        ];
+
+        for (script, error) in errors {
+            let err = parse_input_script(script).err().unwrap();
+            assert!(err.starts_with(error), "{}: {}", script, err);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use sdl2::keyboard::{Keycode, Scancode};
+
+pub const KEYMAP_NAMES: [&str; 4] = ["physical", "qwerty", "qwertz", "azerty"];
+
+// the host keys of the CHIP-8 keys 0 to F in the different keyboard layouts: the same
+// 4x4 block of physical keys, row by row
+const QWERTY_KEYS: &str = "1234qwerasdfzxcv";
+const QWERTZ_KEYS: &str = "1234qwerasdfyxcv";
+const AZERTY_KEYS: &str = "1234azerqsdfwxcv";
+// the same block by physical position
+const PHYSICAL_KEYS: [Scancode; 16] = [
+    Scancode::Num1,
+    Scancode::Num2,
+    Scancode::Num3,
+    Scancode::Num4,
+    Scancode::Q,
+    Scancode::W,
+    Scancode::E,
+    Scancode::R,
+    Scancode::A,
+    Scancode::S,
+    Scancode::D,
+    Scancode::F,
+    Scancode::Z,
+    Scancode::X,
+    Scancode::C,
+    Scancode::V,
+];
+
+// a key of the host keyboard, either by the character it produces in the current layout
+// (keycode) or by its physical position (scancode, named after the US layout)
+#[derive(Clone, Copy, PartialEq)]
+pub enum HostKey {
+    Keycode(Keycode),
+    Scancode(Scancode),
+}
+
+impl HostKey {
+    // parses SDL key names like "W", "Up" or "Keypad 5". prefixed with "scancode:", the
+    // physical key at this position of a US keyboard is meant.
+    pub fn from_name(name: &str) -> Option<HostKey> {
+        match name.strip_prefix("scancode:") {
+            Some(name) => Scancode::from_name(name).map(HostKey::Scancode),
+            None => Keycode::from_name(name).map(HostKey::Keycode),
+        }
+    }
+
+    fn matches(&self, keycode: Option<Keycode>, scancode: Option<Scancode>) -> bool {
+        match *self {
+            HostKey::Keycode(key) => keycode == Some(key),
+            HostKey::Scancode(key) => scancode == Some(key),
+        }
+    }
+}
+
+// the host keys of every CHIP-8 key. a CHIP-8 key can have several host keys, it is
+// pressed as long as one of them is held.
+#[derive(Clone)]
+pub struct Keymap {
+    // indexed by the CHIP-8 key
+    keys: Vec<Vec<HostKey>>,
+}
+
+fn layout_keys(layout: &str) -> &'static str {
+    match layout {
+        "qwertz" => QWERTZ_KEYS,
+        "azerty" => AZERTY_KEYS,
+        _ => QWERTY_KEYS,
+    }
+}
+
+impl Keymap {
+    // "physical" maps the keys by their position regardless of the keyboard layout,
+    // the other presets by the characters printed on the keys
+    pub fn preset(name: &str) -> Option<Keymap> {
+        if !KEYMAP_NAMES.contains(&name) {
+            return None;
+        }
+
+        let mut keymap = Keymap {
+            keys: vec![Vec::new(); 16],
+        };
+        for (key, character) in layout_keys(name).chars().enumerate() {
+            let host_key = if name == "physical" {
+                HostKey::Scancode(PHYSICAL_KEYS[key])
+            } else {
+                // the keycodes of digits and letters are their (lower case) characters
+                HostKey::Keycode(Keycode::from_i32(character as i32).unwrap())
+            };
+            keymap.keys[key].push(host_key);
+        }
+
+        Some(keymap)
+    }
+
+    // the characters of the keys 0 to F for the terminal frontend, which only receives
+    // characters. physical is taken as QWERTY there, as the scancodes of the window are
+    // named after the US layout.
+    pub fn terminal_keys(name: &str) -> &'static str {
+        layout_keys(name)
+    }
+
+    // replaces the host keys of a CHIP-8 key
+    pub fn bind(&mut self, key: u8, host_keys: Vec<HostKey>) {
+        self.keys[key as usize] = host_keys;
+    }
+
+    // the CHIP-8 keys the host key is mapped to
+    pub fn chip8_keys(&self, keycode: Option<Keycode>, scancode: Option<Scancode>) -> Vec<u8> {
+        (0..16)
+            .filter(|&key| {
+                self.keys[key as usize]
+                    .iter()
+                    .any(|host_key| host_key.matches(keycode, scancode))
+            })
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::preset("physical").unwrap()
+    }
+}
@@ -0,0 +1,51 @@
+use sdl2::rect::Rect;
+
+// the panel is a square beside the display, its width in CHIP-8 pixels
+pub const KEYPAD_PANEL_WIDTH: u32 = 32;
+
+// the keys as laid out on the hex keypad of the COSMAC VIP, row by row
+pub const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xc, 0x4, 0x5, 0x6, 0xd, 0x7, 0x8, 0x9, 0xe, 0xa, 0x0, 0xb, 0xf,
+];
+
+// the rectangle of the key at the given index of KEYPAD_LAYOUT, inside the panel. the keys
+// are separated by gaps of an eighth of their size.
+pub fn key_rect(panel: Rect, index: usize) -> Rect {
+    let cell_width = panel.width() / 4;
+    let cell_height = panel.height() / 4;
+    let gap_x = cell_width / 8;
+    let gap_y = cell_height / 8;
+
+    Rect::new(
+        panel.x() + ((index % 4) as u32 * cell_width + gap_x / 2) as i32,
+        panel.y() + ((index / 4) as u32 * cell_height + gap_y / 2) as i32,
+        (cell_width - gap_x).max(1),
+        (cell_height - gap_y).max(1),
+    )
+}
+
+// where the panel is in the window, used to find the keys under the mouse or a finger
+#[derive(Clone, Copy)]
+pub struct KeypadArea {
+    // in window coordinates, which differ from the pixels of the output on high-DPI screens
+    pub panel: Rect,
+    pub window_width: u32,
+    pub window_height: u32,
+}
+
+impl KeypadArea {
+    // the key at a position in window coordinates, if any
+    pub fn key_at(&self, x: i32, y: i32) -> Option<u8> {
+        (0..16)
+            .find(|&index| key_rect(self.panel, index).contains_point((x, y)))
+            .map(|index| KEYPAD_LAYOUT[index])
+    }
+
+    // the key at a position given relative to the window size, as touch events are
+    pub fn key_at_relative(&self, x: f32, y: f32) -> Option<u8> {
+        self.key_at(
+            (x * self.window_width as f32) as i32,
+            (y * self.window_height as f32) as i32,
+        )
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::{Address, MEMORY_SIZE};
+
+const VIP_STACK_DEPTH: usize = 12;
+const SCHIP_STACK_DEPTH: usize = 16;
+
+// behavior of a ROM which is undefined or differs between CHIP-8 interpreters
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hazard {
+    UninitializedMemoryRead(Address),
+    VfAsOperand,
+    IndexOverflowIntoVf,
+    StackDepth(usize),
+    SpriteOutsideMemory,
+    SpritePartiallyOffscreen,
+    MachineCodeCall(Address),
+}
+
+impl Hazard {
+    // hazards of the same kind are counted together in the summary
+    fn kind(&self) -> &'static str {
+        match self {
+            Hazard::UninitializedMemoryRead(_) => "reads of memory never written",
+            Hazard::VfAsOperand => "VF used as operand of 8XY4/8XY5",
+            Hazard::IndexOverflowIntoVf => "FX1E overflows of I into VF",
+            Hazard::StackDepth(_) => "stack depth beyond 12/16",
+            Hazard::SpriteOutsideMemory => "DXYN reading beyond 4 KiB",
+            Hazard::SpritePartiallyOffscreen => "sprites drawn partially offscreen",
+            Hazard::MachineCodeCall(_) => "0NNN machine code calls",
+        }
+    }
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Hazard::UninitializedMemoryRead(address) => {
+                write!(
+                    f,
+                    "reads memory at {:#05x}, which was never written",
+                    address
+                )
+            }
+            Hazard::VfAsOperand => write!(
+                f,
+                "uses VF as operand of 8XY4/8XY5, the result depends on when VF is set"
+            ),
+            Hazard::IndexOverflowIntoVf => write!(
+                f,
+                "FX1E overflows I beyond 0xfff, setting VF is an Amiga-interpreter quirk"
+            ),
+            Hazard::StackDepth(depth) if depth > SCHIP_STACK_DEPTH => write!(
+                f,
+                "stack depth of {} exceeds the 16 levels of most interpreters",
+                depth
+            ),
+            Hazard::StackDepth(depth) => write!(
+                f,
+                "stack depth of {} exceeds the 12 levels of the COSMAC VIP",
+                depth
+            ),
+            Hazard::SpriteOutsideMemory => {
+                write!(f, "DXYN reads sprite data beyond the 4 KiB of memory")
+            }
+            Hazard::SpritePartiallyOffscreen => write!(
+                f,
+                "sprite is drawn partially offscreen, interpreters either clip or wrap it"
+            ),
+            Hazard::MachineCodeCall(address) => write!(
+                f,
+                "calls machine code at {:#05x} (0NNN), which is ignored",
+                address
+            ),
+        }
+    }
+}
+
+// collects the hazards found in --strict mode. every hazard is reported the first time it
+// occurs at a given program counter, all occurrences are counted for the summary.
+pub struct Linter {
+    written: [bool; MEMORY_SIZE as usize],
+    reported: BTreeSet<(Address, Hazard)>,
+    occurrences: BTreeMap<&'static str, u64>,
+}
+
+impl Linter {
+    pub fn init() -> Linter {
+        Linter {
+            written: [false; MEMORY_SIZE as usize],
+            reported: BTreeSet::new(),
+            occurrences: BTreeMap::new(),
+        }
+    }
+
+    // counts the hazard and returns the warning to print if it is new at this program counter
+    pub fn report(
+        &mut self,
+        program_counter: Address,
+        cycle: u64,
+        hazard: Hazard,
+    ) -> Option<String> {
+        *self.occurrences.entry(hazard.kind()).or_insert(0) += 1;
+
+        if !self.reported.insert((program_counter, hazard)) {
+            return None;
+        }
+        Some(format!(
+            "STRICT: cycle {}, PC {:#05x}: {}",
+            cycle, program_counter, hazard
+        ))
+    }
+
+    pub fn mark_written(&mut self, start_address: Address, length: u16) {
+        for address in start_address..start_address.saturating_add(length).min(MEMORY_SIZE) {
+            self.written[address as usize] = true;
+        }
+    }
+
+    pub fn check_read(
+        &mut self,
+        program_counter: Address,
+        cycle: u64,
+        start_address: Address,
+        length: u16,
+    ) -> Option<String> {
+        let end_address = start_address.saturating_add(length).min(MEMORY_SIZE);
+        let address =
+            (start_address..end_address).find(|&address| !self.written[address as usize])?;
+        self.report(
+            program_counter,
+            cycle,
+            Hazard::UninitializedMemoryRead(address),
+        )
+    }
+
+    pub fn check_stack_depth(
+        &mut self,
+        program_counter: Address,
+        cycle: u64,
+        depth: usize,
+    ) -> Option<String> {
+        if depth <= VIP_STACK_DEPTH {
+            return None;
+        }
+        self.report(program_counter, cycle, Hazard::StackDepth(depth))
+    }
+
+    pub fn print_summary(&self) {
+        if self.occurrences.is_empty() {
+            println!("STRICT: no portability hazards found");
+            return;
+        }
+
+        println!("STRICT: summary of portability hazards:");
+        for (kind, count) in self.occurrences.iter() {
+            println!("    {:>8}x {}", count, kind);
+        }
+    }
+}
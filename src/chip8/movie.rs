@@ -0,0 +1,256 @@
+use std::fmt;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(std::io::Error),
+    NotAMovie,
+    UnsupportedVersion(u16),
+    RomMismatch,
+    Truncated,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Io(err) => write!(f, "{}", err),
+            MovieError::NotAMovie => write!(f, "file is not a CHIP-8 input movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "movie has version {}, but only version {} is supported",
+                version, VERSION
+            ),
+            MovieError::RomMismatch => write!(f, "movie was recorded with a different ROM"),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+        }
+    }
+}
+
+impl From<std::io::Error> for MovieError {
+    fn from(err: std::io::Error) -> MovieError {
+        MovieError::Io(err)
+    }
+}
+
+// the keypad from the given cycle of a frame on, both counted from 0
+#[derive(Clone, Copy, PartialEq)]
+struct KeypadChange {
+    frame: u32,
+    cycle: u32,
+    mask: u16,
+}
+
+// everything needed to reproduce a run: the seed of the random number generator, the
+// settings, the RPL user flags the run started with and every change of the keypad, at
+// the cycle of the frame it happened in. the hash of the final machine state is stored
+// to verify the playback.
+// on disk, all values are stored little-endian, the keypad as a 16-bit mask of the keys.
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub frequency: u32,
+    pub final_state_hash: u64,
+    pub rpl_flags: [u8; 16],
+    frame_count: u32,
+    changes: Vec<KeypadChange>,
+}
+
+fn read_bytes<'a>(
+    bytes: &'a [u8],
+    position: &mut usize,
+    length: usize,
+) -> Result<&'a [u8], MovieError> {
+    let read = bytes
+        .get(*position..*position + length)
+        .ok_or(MovieError::Truncated)?;
+    *position += length;
+    Ok(read)
+}
+
+fn read_u16(bytes: &[u8], position: &mut usize) -> Result<u16, MovieError> {
+    Ok(u16::from_le_bytes(
+        read_bytes(bytes, position, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(bytes: &[u8], position: &mut usize) -> Result<u32, MovieError> {
+    Ok(u32::from_le_bytes(
+        read_bytes(bytes, position, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(bytes: &[u8], position: &mut usize) -> Result<u64, MovieError> {
+    Ok(u64::from_le_bytes(
+        read_bytes(bytes, position, 8)?.try_into().unwrap(),
+    ))
+}
+
+fn to_keypad(mask: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (key, is_pressed) in keypad.iter_mut().enumerate() {
+        *is_pressed = mask & 1 << key != 0;
+    }
+    keypad
+}
+
+impl Movie {
+    pub fn init(rom_hash: u64, seed: u64, frequency: u32, rpl_flags: [u8; 16]) -> Movie {
+        Movie {
+            rom_hash,
+            seed,
+            frequency,
+            final_state_hash: 0,
+            rpl_flags,
+            frame_count: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count as usize
+    }
+
+    // records the keypad before the given cycle of the current frame, if it changed
+    pub fn record_keypad(&mut self, cycle: u32, keypad: &[bool; 16]) {
+        let mask = (0..16)
+            .filter(|&key| keypad[key])
+            .fold(0u16, |mask, key| mask | 1 << key);
+        let last_mask = self.changes.last().map_or(0, |change| change.mask);
+        if mask != last_mask {
+            self.changes.push(KeypadChange {
+                frame: self.frame_count,
+                cycle,
+                mask,
+            });
+        }
+    }
+
+    pub fn finish_frame(&mut self) {
+        self.frame_count += 1;
+    }
+
+    // the keypad before the given cycle of the frame
+    pub fn keypad(&self, frame: usize, cycle: u32) -> [bool; 16] {
+        let time = (frame as u32, cycle);
+        let applied = self
+            .changes
+            .partition_point(|change| (change.frame, change.cycle) <= time);
+        let mask = applied
+            .checked_sub(1)
+            .map_or(0, |index| self.changes[index].mask);
+        to_keypad(mask)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.changes.len() * 10 + 64);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.rom_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.frequency.to_le_bytes());
+        bytes.extend_from_slice(&self.final_state_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.rpl_flags);
+        bytes.extend_from_slice(&self.frame_count.to_le_bytes());
+        bytes.extend_from_slice(&(self.changes.len() as u32).to_le_bytes());
+        for change in self.changes.iter() {
+            bytes.extend_from_slice(&change.frame.to_le_bytes());
+            bytes.extend_from_slice(&change.cycle.to_le_bytes());
+            bytes.extend_from_slice(&change.mask.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Movie, MovieError> {
+        let mut position = 0;
+
+        if read_bytes(bytes, &mut position, 4).map_err(|_| MovieError::NotAMovie)? != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = read_u16(bytes, &mut position)?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let rom_hash = read_u64(bytes, &mut position)?;
+        let seed = read_u64(bytes, &mut position)?;
+        let frequency = read_u32(bytes, &mut position)?;
+        let final_state_hash = read_u64(bytes, &mut position)?;
+        let mut rpl_flags = [0; 16];
+        rpl_flags.copy_from_slice(read_bytes(bytes, &mut position, 16)?);
+        let frame_count = read_u32(bytes, &mut position)?;
+
+        let mut movie = Movie {
+            rom_hash,
+            seed,
+            frequency,
+            final_state_hash,
+            rpl_flags,
+            frame_count,
+            changes: Vec::new(),
+        };
+
+        let change_count = read_u32(bytes, &mut position)?;
+        for _ in 0..change_count {
+            movie.changes.push(KeypadChange {
+                frame: read_u32(bytes, &mut position)?,
+                cycle: read_u32(bytes, &mut position)?,
+                mask: read_u16(bytes, &mut position)?,
+            });
+        }
+
+        Ok(movie)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), MovieError> {
+        std::fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Movie, MovieError> {
+        Movie::decode(&std::fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypad(keys: &[usize]) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for &key in keys {
+            keypad[key] = true;
+        }
+        keypad
+    }
+
+    #[test]
+    fn plays_back_changes_within_frames() {
+        let mut movie = Movie::init(1, 2, 700, [3; 16]);
+        movie.record_keypad(0, &keypad(&[]));
+        movie.finish_frame();
+        movie.record_keypad(0, &keypad(&[5]));
+        movie.record_keypad(4, &keypad(&[5]));
+        movie.record_keypad(7, &keypad(&[5, 0xa]));
+        movie.finish_frame();
+        movie.record_keypad(3, &keypad(&[]));
+        movie.finish_frame();
+
+        // unchanged keypads are not stored
+        assert_eq!(movie.changes.len(), 3);
+
+        let movie = Movie::decode(&movie.encode()).unwrap();
+        assert_eq!(movie.rpl_flags, [3; 16]);
+        assert_eq!(movie.frame_count(), 3);
+        assert_eq!(movie.keypad(0, 11), keypad(&[]));
+        assert_eq!(movie.keypad(1, 0), keypad(&[5]));
+        assert_eq!(movie.keypad(1, 6), keypad(&[5]));
+        assert_eq!(movie.keypad(1, 7), keypad(&[5, 0xa]));
+        assert_eq!(movie.keypad(2, 2), keypad(&[5, 0xa]));
+        assert_eq!(movie.keypad(2, 3), keypad(&[]));
+    }
+}
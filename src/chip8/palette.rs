@@ -0,0 +1,69 @@
+use sdl2::pixels::Color;
+
+// colors of the display: the background, the pixels of the first plane, the pixels of the
+// second plane and the pixels set in both planes. as long as only one plane is drawn,
+// only the first two are used.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+pub const THEME_NAMES: [&str; 6] = [
+    "classic",
+    "green-phosphor",
+    "amber",
+    "lcd",
+    "octo",
+    "high-contrast",
+];
+
+const fn rgb(color: u32) -> Color {
+    Color::RGB((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+// parses colors given as RRGGBB, optionally prefixed with # or 0x
+pub fn parse_color(color: &str) -> Option<Color> {
+    let color = color.trim();
+    let hex = color
+        .strip_prefix('#')
+        .or_else(|| color.strip_prefix("0x"))
+        .unwrap_or(color);
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(rgb)
+}
+
+impl Palette {
+    pub fn theme(name: &str) -> Option<Palette> {
+        let colors = match name {
+            "classic" => [rgb(0x000000), rgb(0xffffff), rgb(0xaaaaaa), rgb(0x555555)],
+            "green-phosphor" => [rgb(0x0a140a), rgb(0x33ff66), rgb(0x1a8033), rgb(0xb3ffc6)],
+            "amber" => [rgb(0x140c00), rgb(0xffb000), rgb(0x805800), rgb(0xffd780)],
+            "lcd" => [rgb(0x9bbc0f), rgb(0x0f380f), rgb(0x306230), rgb(0x8bac0f)],
+            // the defaults of the Octo IDE
+            "octo" => [rgb(0x996600), rgb(0xffcc00), rgb(0xff6600), rgb(0x662200)],
+            "high-contrast" => [rgb(0x000000), rgb(0xffffff), rgb(0xffff00), rgb(0x00ffff)],
+            _ => return None,
+        };
+
+        Some(Palette { colors })
+    }
+
+    // converts a display buffer to an RGB image (3 bytes per pixel, row by row)
+    pub fn render(&self, display_buffer: &[bool]) -> Vec<u8> {
+        display_buffer
+            .iter()
+            .flat_map(|&is_set| {
+                let color = self.colors[is_set as usize];
+                [color.r, color.g, color.b]
+            })
+            .collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::theme("classic").unwrap()
+    }
+}
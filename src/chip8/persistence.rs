@@ -0,0 +1,70 @@
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+const DISPLAY_SIZE: usize = (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize;
+
+// intensities below this are shown as fully off
+const MINIMUM_INTENSITY: f32 = 1.0 / 255.0;
+
+// CHIP-8 games erase and redraw sprites with XOR, so moving sprites are off in every other
+// frame and flicker. these modes emulate the afterglow of a phosphor screen to hide it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Persistence {
+    Off,
+    // a pixel is lit if it is set in the current or the last frame
+    LastTwoFrames,
+    // a pixel turned off fades out, keeping this share of its intensity every frame
+    Decay(f32),
+}
+
+// the history of the displayed frames. it is only used when converting the display buffer
+// to the output image, so the emulation itself is unaffected.
+pub struct PersistenceFilter {
+    mode: Persistence,
+    previous_frame: [bool; DISPLAY_SIZE],
+    intensities: [f32; DISPLAY_SIZE],
+}
+
+impl PersistenceFilter {
+    pub fn init(mode: Persistence) -> PersistenceFilter {
+        PersistenceFilter {
+            mode,
+            previous_frame: [false; DISPLAY_SIZE],
+            intensities: [0.0; DISPLAY_SIZE],
+        }
+    }
+
+    // returns the intensity (0.0 to 1.0) of every pixel for the next output frame
+    pub fn apply(&mut self, frame: &[bool]) -> &[f32; DISPLAY_SIZE] {
+        for (i, &is_set) in frame.iter().enumerate() {
+            self.intensities[i] = match self.mode {
+                Persistence::Off => is_set as u8 as f32,
+                Persistence::LastTwoFrames => (is_set || self.previous_frame[i]) as u8 as f32,
+                Persistence::Decay(_) if is_set => 1.0,
+                Persistence::Decay(decay) => {
+                    let intensity = self.intensities[i] * decay;
+                    if intensity < MINIMUM_INTENSITY {
+                        0.0
+                    } else {
+                        intensity
+                    }
+                }
+            };
+        }
+        self.previous_frame.copy_from_slice(frame);
+
+        &self.intensities
+    }
+
+    // whether applying the filter to an unchanged frame would still change the output,
+    // because pixels are fading out or the last frame differed
+    pub fn is_changing(&self) -> bool {
+        match self.mode {
+            Persistence::Off => false,
+            Persistence::LastTwoFrames | Persistence::Decay(_) => self
+                .previous_frame
+                .iter()
+                .zip(self.intensities.iter())
+                .any(|(&is_set, &intensity)| intensity != is_set as u8 as f32),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// a small xorshift64* generator. unlike the thread-local generator of the rand crate its
+// whole state is a single number, so it can be stored in save states and reproduced.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn init(seed: u64) -> Random {
+        // xorshift never leaves the state zero, so zero must not be used as seed
+        Random {
+            state: if seed == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+    }
+}
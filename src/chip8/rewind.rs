@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+
+// a ring buffer of encoded machine states. only the newest state is kept as a whole, older
+// ones are stored as run-length encoded XOR against their successor. as a frame usually
+// changes only a few bytes of the 4 KiB memory, these deltas are tiny.
+pub struct RewindBuffer {
+    capacity: usize,
+    granularity: u32,
+    frames_since_snapshot: u32,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let length = a.len().max(b.len());
+    (0..length)
+        .map(|i| a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0))
+        .collect()
+}
+
+// encodes the length of the original state, followed by pairs of zero-runs and literal
+// runs: [zero count: u16][literal count: u16][literals]...
+fn compress(previous: &[u8], bytes: &[u8]) -> Vec<u8> {
+    let mut compressed = (previous.len() as u32).to_le_bytes().to_vec();
+
+    let mut position = 0;
+    while position < bytes.len() {
+        let zeros = bytes[position..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|&&byte| byte == 0)
+            .count();
+        position += zeros;
+        let literals = bytes[position..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|&&byte| byte != 0)
+            .count();
+
+        compressed.extend_from_slice(&(zeros as u16).to_le_bytes());
+        compressed.extend_from_slice(&(literals as u16).to_le_bytes());
+        compressed.extend_from_slice(&bytes[position..position + literals]);
+        position += literals;
+    }
+
+    compressed
+}
+
+// restores the state preceding `latest` from a delta created by compress()
+fn decompress(latest: &[u8], delta: &[u8]) -> Vec<u8> {
+    let length = u32::from_le_bytes(delta[0..4].try_into().unwrap()) as usize;
+    let mut bytes = Vec::with_capacity(length.max(latest.len()));
+
+    let mut position = 4;
+    while position < delta.len() {
+        let zeros = u16::from_le_bytes([delta[position], delta[position + 1]]) as usize;
+        let literals = u16::from_le_bytes([delta[position + 2], delta[position + 3]]) as usize;
+        position += 4;
+
+        bytes.resize(bytes.len() + zeros, 0);
+        bytes.extend_from_slice(&delta[position..position + literals]);
+        position += literals;
+    }
+
+    let mut previous = xor(latest, &bytes);
+    previous.truncate(length);
+    previous
+}
+
+impl RewindBuffer {
+    // keeps capacity snapshots, one every granularity frames
+    pub fn init(capacity: usize, granularity: u32) -> RewindBuffer {
+        RewindBuffer {
+            capacity,
+            granularity: granularity.max(1),
+            frames_since_snapshot: 0,
+            latest: None,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn is_snapshot_due(&mut self) -> bool {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < self.granularity {
+            return false;
+        }
+
+        self.frames_since_snapshot = 0;
+        true
+    }
+
+    // while rewinding, a snapshot is restored every granularity frames in between which the
+    // display is held, so the game runs backwards at the pace it was played
+    pub fn is_pop_due(&mut self) -> bool {
+        self.is_snapshot_due()
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = self.latest.take() {
+            self.deltas
+                .push_back(compress(&latest, &xor(&latest, &state)));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+
+        self.latest = Some(state);
+    }
+
+    // removes the newest snapshot and returns the one before it
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        let previous = decompress(self.latest.as_ref()?, &delta);
+        self.latest = Some(previous.clone());
+        self.frames_since_snapshot = 0;
+
+        Some(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // states of different lengths that differ in a few bytes, like consecutive frames
+    fn states() -> Vec<Vec<u8>> {
+        (0..6u8)
+            .map(|i| {
+                let mut state = vec![0x5a; 4096 + i as usize % 2];
+                state[i as usize * 100] = i;
+                state[4000] = i.wrapping_mul(37);
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compress_round_trips() {
+        let states = states();
+        for pair in states.windows(2) {
+            let (previous, latest) = (&pair[0], &pair[1]);
+            let delta = compress(previous, &xor(previous, latest));
+            assert!(delta.len() < 64);
+            assert_eq!(&decompress(latest, &delta), previous);
+        }
+
+        // runs longer than a u16 are split
+        let zeros = vec![0; 70_000];
+        let ones = vec![1; 70_000];
+        assert_eq!(
+            decompress(&ones, &compress(&zeros, &xor(&zeros, &ones))),
+            zeros
+        );
+    }
+
+    #[test]
+    fn pops_the_pushed_states_in_reverse() {
+        let states = states();
+        let mut buffer = RewindBuffer::init(10, 1);
+        for state in states.iter() {
+            buffer.push(state.clone());
+        }
+
+        for state in states[..states.len() - 1].iter().rev() {
+            assert_eq!(buffer.pop().as_ref(), Some(state));
+        }
+        assert_eq!(buffer.pop(), None);
+
+        // pushing after popping continues from the restored state
+        buffer.push(states[3].clone());
+        assert_eq!(buffer.pop().as_ref(), Some(&states[0]));
+    }
+
+    #[test]
+    fn drops_the_oldest_states_beyond_capacity() {
+        let states = states();
+        let mut buffer = RewindBuffer::init(2, 1);
+        for state in states.iter() {
+            buffer.push(state.clone());
+        }
+
+        assert_eq!(buffer.pop().as_ref(), Some(&states[4]));
+        assert_eq!(buffer.pop().as_ref(), Some(&states[3]));
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn pops_at_the_pace_of_the_snapshots() {
+        let mut buffer = RewindBuffer::init(10, 3);
+        let due: Vec<bool> = (0..6).map(|_| buffer.is_pop_due()).collect();
+        assert_eq!(due, vec![false, false, true, false, false, true]);
+    }
+}
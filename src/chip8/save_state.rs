@@ -0,0 +1,381 @@
+use std::fmt;
+use std::path::Path;
+
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::{Address, KeyWait, Register, MEMORY_SIZE};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u16 = 1;
+// stored instead of a register or key when there is none
+const NONE: u8 = 0xff;
+// the depth of the stack is stored as u16
+const MAX_STACK_DEPTH: usize = u16::MAX as usize;
+
+const DISPLAY_SIZE: usize = (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize;
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(std::io::Error),
+    NotASaveState,
+    UnsupportedVersion(u16),
+    RomMismatch,
+    Truncated,
+    // names the value that would make the emulator fail
+    OutOfRange(&'static str),
+    StackTooDeep(usize),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::Io(err) => write!(f, "{}", err),
+            SaveStateError::NotASaveState => write!(f, "file is not a CHIP-8 save state"),
+            SaveStateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state has version {}, but only version {} is supported",
+                version, VERSION
+            ),
+            SaveStateError::RomMismatch => write!(f, "save state belongs to a different ROM"),
+            SaveStateError::Truncated => write!(f, "save state is truncated"),
+            SaveStateError::OutOfRange(value) => {
+                write!(f, "save state is corrupt: {} is out of range", value)
+            }
+            SaveStateError::StackTooDeep(depth) => write!(
+                f,
+                "stack of depth {} is too deep to be stored (at most {})",
+                depth, MAX_STACK_DEPTH
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveStateError {
+    fn from(err: std::io::Error) -> SaveStateError {
+        SaveStateError::Io(err)
+    }
+}
+
+// the complete state of the machine.
+// on disk it is stored little-endian after a header of magic, version and ROM hash.
+#[derive(Clone)]
+pub struct MachineState {
+    pub rom_hash: u64,
+    pub data_registers: [Register; 16],
+    pub memory: [u8; MEMORY_SIZE as usize],
+    pub program_counter: Address,
+    pub index_register: Address,
+    pub stack: Vec<Address>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub random_state: u64,
+    pub display_buffer: [bool; DISPLAY_SIZE],
+    pub key_states: [bool; 16],
+    pub key_wait: Option<KeyWait>,
+    pub rpl_flags: [Register; 16],
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(SaveStateError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+}
+
+impl MachineState {
+    // fails for a stack too deep to be stored, which only a runaway recursion reaches
+    pub fn encode(&self) -> Result<Vec<u8>, SaveStateError> {
+        if self.stack.len() > MAX_STACK_DEPTH {
+            return Err(SaveStateError::StackTooDeep(self.stack.len()));
+        }
+
+        let mut bytes = Vec::with_capacity(MEMORY_SIZE as usize + 512);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.rom_hash.to_le_bytes());
+
+        bytes.extend_from_slice(&self.program_counter.to_le_bytes());
+        bytes.extend_from_slice(&self.index_register.to_le_bytes());
+        bytes.extend_from_slice(&self.data_registers);
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.extend_from_slice(&self.random_state.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.stack.len() as u16).to_le_bytes());
+        for address in self.stack.iter() {
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+
+        let key_states = (0..16)
+            .filter(|&key| self.key_states[key])
+            .fold(0u16, |mask, key| mask | 1 << key);
+        bytes.extend_from_slice(&key_states.to_le_bytes());
+
+        // the display buffer is packed to one bit per pixel, most significant bit first
+        for pixels in self.display_buffer.chunks(8) {
+            let byte = pixels
+                .iter()
+                .enumerate()
+                .filter(|(_, &is_set)| is_set)
+                .fold(0u8, |byte, (i, _)| byte | 128 >> i);
+            bytes.push(byte);
+        }
+
+        bytes.extend_from_slice(&self.memory);
+
+        match self.key_wait {
+            Some(key_wait) => {
+                bytes.push(key_wait.register);
+                bytes.push(key_wait.pressed_key.unwrap_or(NONE));
+            }
+            None => bytes.extend_from_slice(&[NONE, NONE]),
+        }
+
+        bytes.extend_from_slice(&self.rpl_flags);
+
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<MachineState, SaveStateError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader
+            .read_bytes(4)
+            .map_err(|_| SaveStateError::NotASaveState)?
+            != MAGIC
+        {
+            return Err(SaveStateError::NotASaveState);
+        }
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let rom_hash = reader.read_u64()?;
+
+        let program_counter = reader.read_u16()?;
+        let index_register = reader.read_u16()?;
+        let mut data_registers = [0; 16];
+        data_registers.copy_from_slice(reader.read_bytes(16)?);
+        let delay_timer = reader.read_u8()?;
+        let sound_timer = reader.read_u8()?;
+        let random_state = reader.read_u64()?;
+
+        let stack_depth = reader.read_u16()?;
+        let stack = (0..stack_depth)
+            .map(|_| reader.read_u16())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let key_mask = reader.read_u16()?;
+        let mut key_states = [false; 16];
+        for (key, is_pressed) in key_states.iter_mut().enumerate() {
+            *is_pressed = key_mask & 1 << key != 0;
+        }
+
+        let mut display_buffer = [false; DISPLAY_SIZE];
+        let packed_display = reader.read_bytes(DISPLAY_SIZE / 8)?;
+        for (i, is_set) in display_buffer.iter_mut().enumerate() {
+            *is_set = packed_display[i / 8] & 128 >> (i % 8) != 0;
+        }
+
+        let mut memory = [0; MEMORY_SIZE as usize];
+        memory.copy_from_slice(reader.read_bytes(MEMORY_SIZE as usize)?);
+
+        let register = reader.read_u8()?;
+        let pressed_key = reader.read_u8()?;
+        let key_wait = (register != NONE).then_some(KeyWait {
+            register,
+            pressed_key: (pressed_key != NONE).then_some(pressed_key),
+        });
+
+        let mut rpl_flags = [0; 16];
+        rpl_flags.copy_from_slice(reader.read_bytes(16)?);
+
+        let state = MachineState {
+            rom_hash,
+            data_registers,
+            memory,
+            program_counter,
+            index_register,
+            stack,
+            delay_timer,
+            sound_timer,
+            random_state,
+            display_buffer,
+            key_states,
+            key_wait,
+            rpl_flags,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+
+    // rejects values the emulator would panic on: addresses outside the memory (the program
+    // counter and return addresses need room for a whole instruction), registers or keys
+    // beyond F and a stack deeper than encode stores
+    fn validate(&self) -> Result<(), SaveStateError> {
+        let is_instruction_address = |address: Address| address < MEMORY_SIZE - 1;
+
+        if !is_instruction_address(self.program_counter) {
+            return Err(SaveStateError::OutOfRange("program counter"));
+        }
+        if self.index_register >= MEMORY_SIZE {
+            return Err(SaveStateError::OutOfRange("index register"));
+        }
+        if self.stack.len() > MAX_STACK_DEPTH {
+            return Err(SaveStateError::OutOfRange("stack depth"));
+        }
+        if !self
+            .stack
+            .iter()
+            .all(|&address| is_instruction_address(address))
+        {
+            return Err(SaveStateError::OutOfRange("stack entry"));
+        }
+        if let Some(key_wait) = self.key_wait {
+            if key_wait.register >= 16 {
+                return Err(SaveStateError::OutOfRange("FX0A register"));
+            }
+            if key_wait.pressed_key.is_some_and(|key| key >= 16) {
+                return Err(SaveStateError::OutOfRange("FX0A key"));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), SaveStateError> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, self.encode()?)?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<MachineState, SaveStateError> {
+        MachineState::decode(&std::fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_state() -> MachineState {
+        let mut memory = [0; MEMORY_SIZE as usize];
+        memory[0x200..0x204].copy_from_slice(&[0x60, 0x2a, 0xf1, 0x0a]);
+        let mut display_buffer = [false; DISPLAY_SIZE];
+        display_buffer[0] = true;
+        display_buffer[DISPLAY_SIZE - 1] = true;
+        let mut key_states = [false; 16];
+        key_states[0xa] = true;
+
+        MachineState {
+            rom_hash: 0x0123_4567_89ab_cdef,
+            data_registers: [0x2a; 16],
+            memory,
+            program_counter: 0x202,
+            index_register: 0x300,
+            stack: vec![0x204, 0x210],
+            delay_timer: 10,
+            sound_timer: 3,
+            random_state: 42,
+            display_buffer,
+            key_states,
+            key_wait: Some(KeyWait {
+                register: 1,
+                pressed_key: Some(0xa),
+            }),
+            rpl_flags: [7; 16],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_encode_and_decode() {
+        let state = sample_state();
+        let decoded = MachineState::decode(&state.encode().unwrap()).unwrap();
+
+        assert_eq!(decoded.encode().unwrap(), state.encode().unwrap());
+        assert_eq!(decoded.program_counter, 0x202);
+        assert_eq!(decoded.stack, vec![0x204, 0x210]);
+        assert!(decoded.display_buffer[0] && decoded.display_buffer[DISPLAY_SIZE - 1]);
+        assert!(decoded.key_states[0xa] && !decoded.key_states[0xb]);
+        assert!(decoded.key_wait == state.key_wait);
+        assert_eq!(decoded.rpl_flags, [7; 16]);
+    }
+
+    #[test]
+    fn stores_deep_stacks() {
+        let mut state = sample_state();
+        state.stack = vec![0x204; 300];
+        let decoded = MachineState::decode(&state.encode().unwrap()).unwrap();
+        assert_eq!(decoded.stack.len(), 300);
+
+        state.stack = vec![0x204; MAX_STACK_DEPTH + 1];
+        assert!(matches!(
+            state.encode(),
+            Err(SaveStateError::StackTooDeep(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let mut bytes = sample_state().encode().unwrap();
+        assert!(matches!(
+            MachineState::decode(&bytes[..bytes.len() - 1]),
+            Err(SaveStateError::Truncated)
+        ));
+
+        bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            MachineState::decode(&bytes),
+            Err(SaveStateError::UnsupportedVersion(_))
+        ));
+
+        bytes[0] = b'X';
+        assert!(matches!(
+            MachineState::decode(&bytes),
+            Err(SaveStateError::NotASaveState)
+        ));
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let corruptions: [fn(&mut MachineState); 5] = [
+            |state| state.program_counter = MEMORY_SIZE - 1,
+            |state| state.index_register = MEMORY_SIZE,
+            |state| state.stack.push(0xfff),
+            |state| state.key_wait.as_mut().unwrap().register = 16,
+            |state| state.key_wait.as_mut().unwrap().pressed_key = Some(16),
+        ];
+
+        for corrupt in corruptions {
+            let mut state = sample_state();
+            corrupt(&mut state);
+            assert!(matches!(
+                MachineState::decode(&state.encode().unwrap()),
+                Err(SaveStateError::OutOfRange(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::crt_filter::scale_image;
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::image::write_png;
+use super::palette::Palette;
+
+// the UTC time given since the unix epoch as YYYYMMDD-HHMMSS-mmm
+fn timestamp(since_epoch: Duration) -> String {
+    let seconds = since_epoch.as_secs();
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // converts days since 1970-01-01 to a date in the proleptic gregorian calendar
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// the ROM name and the current time, with a counter appended if a screenshot of that name
+// already exists
+fn unused_name(directory: &Path, rom_name: &str) -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name = format!("{}_{}", rom_name, timestamp(since_epoch));
+
+    (1..)
+        .map(|counter| match counter {
+            1 => name.clone(),
+            _ => format!("{}_{}", name, counter),
+        })
+        .find(|name| !directory.join(format!("{}.png", name)).exists())
+        .unwrap()
+}
+
+// saves the display buffer in the colors of the palette as two PNG-files, one at the
+// native resolution and one scaled by window_scale. the files are named after the ROM and
+// the current time (to the millisecond). returns the paths of the written files.
+pub fn save_screenshot(
+    directory: &Path,
+    rom_name: &str,
+    display_buffer: &[bool],
+    palette: &Palette,
+    window_scale: u32,
+) -> Result<Vec<PathBuf>, png::EncodingError> {
+    std::fs::create_dir_all(directory)?;
+
+    let name = unused_name(directory, rom_name);
+    let native_path = directory.join(format!("{}.png", name));
+    let scaled_path = directory.join(format!("{}_{}x.png", name, window_scale));
+
+    let image = palette.render(display_buffer);
+    write_png(
+        &native_path.to_string_lossy(),
+        DISPLAY_WIDTH,
+        DISPLAY_HEIGHT,
+        &image,
+    )?;
+    let scaled = scale_image(
+        &image,
+        DISPLAY_WIDTH as usize,
+        DISPLAY_HEIGHT as usize,
+        window_scale as usize,
+    );
+    write_png(
+        &scaled_path.to_string_lossy(),
+        DISPLAY_WIDTH * window_scale,
+        DISPLAY_HEIGHT * window_scale,
+        &scaled,
+    )?;
+
+    Ok(vec![native_path, scaled_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_png(path: &Path) -> (png::OutputInfo, Vec<u8>) {
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgb = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgb).unwrap();
+        (info, rgb)
+    }
+
+    #[test]
+    fn formats_the_time() {
+        // 2024-02-29 23:59:58.042
+        let since_epoch = Duration::from_millis(1_709_251_198_042);
+        assert_eq!(timestamp(since_epoch), "20240229-235958-042");
+        assert_eq!(timestamp(Duration::ZERO), "19700101-000000-000");
+    }
+
+    #[test]
+    fn saves_native_and_scaled_png() {
+        let directory =
+            std::env::temp_dir().join(format!("chip8-screenshot-{}", std::process::id()));
+        let palette = Palette::default();
+        let mut display_buffer = vec![false; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize];
+        display_buffer[1] = true;
+
+        let paths = save_screenshot(&directory, "rom", &display_buffer, &palette, 3).unwrap();
+        // a second screenshot in the same millisecond gets its own files
+        let more_paths = save_screenshot(&directory, "rom", &display_buffer, &palette, 3).unwrap();
+
+        let (native, rgb) = read_png(&paths[0]);
+        assert_eq!(
+            (native.width, native.height),
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        );
+        let background = palette.colors[0];
+        let foreground = palette.colors[1];
+        assert_eq!(rgb[0..3], [background.r, background.g, background.b]);
+        assert_eq!(rgb[3..6], [foreground.r, foreground.g, foreground.b]);
+
+        let (scaled, rgb) = read_png(&paths[1]);
+        assert_eq!(
+            (scaled.width, scaled.height),
+            (DISPLAY_WIDTH * 3, DISPLAY_HEIGHT * 3)
+        );
+        assert_eq!(rgb[9..12], [foreground.r, foreground.g, foreground.b]);
+        assert!(paths[1].to_string_lossy().ends_with("_3x.png"));
+
+        assert!(paths.iter().all(|path| !more_paths.contains(path)));
+        assert!(more_paths.iter().all(|path| path.exists()));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
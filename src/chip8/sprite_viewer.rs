@@ -0,0 +1,311 @@
+use std::collections::BTreeSet;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use super::display::sprite_pixel;
+use super::image::write_png;
+use super::{Address, MEMORY_SIZE};
+
+const VIEWER_SCALE_FACTOR: u32 = 4;
+const SPRITES_PER_ROW: u32 = 16;
+const VISIBLE_ROWS: u32 = 8;
+const CELL_GAP: u32 = 2;
+
+const BACKGROUND_COLOR: Color = Color::RGB(0x20, 0x20, 0x20);
+const SELECTION_COLOR: Color = Color::RGB(0x20, 0x40, 0x90);
+const INDEX_REGISTER_COLOR: Color = Color::RGB(0xd0, 0x30, 0x30);
+
+pub struct SpriteViewerConfig {
+    pub start_address: Address,
+    pub end_address: Address,
+    // 1 to 15 shows 8xN sprites, 16 shows 16x16 sprites (2 bytes per row)
+    pub sprite_height: u8,
+}
+
+impl SpriteViewerConfig {
+    fn sprite_width(&self) -> u32 {
+        if self.sprite_height == 16 {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn bytes_per_row(&self) -> usize {
+        self.sprite_width() as usize / 8
+    }
+
+    fn bytes_per_sprite(&self) -> u16 {
+        self.bytes_per_row() as u16 * self.sprite_height as u16
+    }
+}
+
+pub struct SpriteViewer {
+    canvas: sdl2::render::Canvas<sdl2::video::Window>,
+    config: SpriteViewerConfig,
+    follow_index_register: bool,
+    index_register: Address,
+    first_visible_row: u32,
+    selected_sprites: BTreeSet<Address>,
+}
+
+impl SpriteViewer {
+    pub fn init(
+        video_subsystem: &sdl2::VideoSubsystem,
+        config: SpriteViewerConfig,
+    ) -> SpriteViewer {
+        let cell_width = config.sprite_width() * VIEWER_SCALE_FACTOR + CELL_GAP;
+        let cell_height = config.sprite_height as u32 * VIEWER_SCALE_FACTOR + CELL_GAP;
+
+        let window = video_subsystem
+            .window(
+                "CHIP-8 sprite-viewer",
+                SPRITES_PER_ROW * cell_width + CELL_GAP,
+                VISIBLE_ROWS * cell_height + CELL_GAP,
+            )
+            .build()
+            .expect("ERROR: Unable to open window for the sprite-viewer. Exiting...");
+
+        let canvas = window
+            .into_canvas()
+            .accelerated()
+            .build()
+            .expect("ERROR: Unable to create canvas in sprite-viewer window. Exiting...");
+
+        SpriteViewer {
+            canvas,
+            config,
+            follow_index_register: false,
+            index_register: 0,
+            first_visible_row: 0,
+            selected_sprites: BTreeSet::new(),
+        }
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        (
+            self.config.sprite_width() * VIEWER_SCALE_FACTOR + CELL_GAP,
+            self.config.sprite_height as u32 * VIEWER_SCALE_FACTOR + CELL_GAP,
+        )
+    }
+
+    // when following the index register, the viewed range is moved so it starts at I
+    fn viewed_range(&self) -> (Address, Address) {
+        if self.follow_index_register {
+            let length = self.config.end_address - self.config.start_address;
+            let end_address = (self.index_register as u32 + length as u32).min(MEMORY_SIZE as u32);
+            (self.index_register, end_address as Address)
+        } else {
+            (self.config.start_address, self.config.end_address)
+        }
+    }
+
+    fn sprite_addresses(&self) -> Vec<Address> {
+        let (start_address, end_address) = self.viewed_range();
+        (start_address..end_address)
+            .step_by(self.config.bytes_per_sprite() as usize)
+            .collect()
+    }
+
+    fn sprite_at_position(&self, x: i32, y: i32) -> Option<Address> {
+        let (cell_width, cell_height) = self.cell_size();
+        let column = x.max(0) as u32 / cell_width;
+        let row = y.max(0) as u32 / cell_height + self.first_visible_row;
+        if column >= SPRITES_PER_ROW {
+            return None;
+        }
+
+        self.sprite_addresses()
+            .get((row * SPRITES_PER_ROW + column) as usize)
+            .copied()
+    }
+
+    pub fn handle_event(&mut self, event: &Event, memory: &[u8]) {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::F),
+                ..
+            } => {
+                self.follow_index_register = !self.follow_index_register;
+                self.first_visible_row = 0;
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::E),
+                ..
+            } => {
+                self.export_sprites(memory);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Up),
+                ..
+            } => {
+                self.scroll(-1);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Down),
+                ..
+            } => {
+                self.scroll(1);
+            }
+            Event::MouseWheel { y, .. } => {
+                self.scroll(-y);
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(address) = self.sprite_at_position(*x, *y) {
+                    if !self.selected_sprites.remove(&address) {
+                        self.selected_sprites.insert(address);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn scroll(&mut self, rows: i32) {
+        let sprite_count = self.sprite_addresses().len() as u32;
+        let total_rows = sprite_count.div_ceil(SPRITES_PER_ROW);
+        let max_first_row = total_rows.saturating_sub(VISIBLE_ROWS);
+
+        self.first_visible_row =
+            (self.first_visible_row as i32 + rows).clamp(0, max_first_row as i32) as u32;
+    }
+
+    // exports the selected sprites (or all viewed sprites if none is selected) at native
+    // resolution into a single PNG-file, laid out in the same grid as in the viewer.
+    fn export_sprites(&self, memory: &[u8]) {
+        let addresses: Vec<Address> = if self.selected_sprites.is_empty() {
+            self.sprite_addresses()
+        } else {
+            self.selected_sprites.iter().copied().collect()
+        };
+
+        if addresses.is_empty() {
+            return;
+        }
+
+        let sprite_width = self.config.sprite_width();
+        let sprite_height = self.config.sprite_height as u32;
+        let columns = SPRITES_PER_ROW.min(addresses.len() as u32);
+        let rows = (addresses.len() as u32).div_ceil(columns);
+        let width = columns * (sprite_width + 1) - 1;
+        let height = rows * (sprite_height + 1) - 1;
+
+        let mut rgb = vec![0x40; (width * height * 3) as usize];
+        for (i, address) in addresses.iter().enumerate() {
+            let origin_x = (i as u32 % columns) * (sprite_width + 1);
+            let origin_y = (i as u32 / columns) * (sprite_height + 1);
+
+            for y in 0..sprite_height {
+                for x in 0..sprite_width {
+                    let is_set = sprite_pixel(
+                        memory,
+                        *address as usize,
+                        self.config.bytes_per_row(),
+                        x as usize,
+                        y as usize,
+                    );
+                    let offset = (((origin_y + y) * width + origin_x + x) * 3) as usize;
+                    rgb[offset..offset + 3].fill(if is_set { 0xff } else { 0x00 });
+                }
+            }
+        }
+
+        let path = format!("sprites_{:#06x}_{}.png", addresses[0], addresses.len());
+        match write_png(&path, width, height, &rgb) {
+            Ok(()) => println!("Exported {} sprite(s) to {}", addresses.len(), path),
+            Err(err) => eprintln!("ERROR: Could not export sprites to {}: {}", path, err),
+        }
+    }
+
+    pub fn draw(&mut self, memory: &[u8], index_register: Address) {
+        self.index_register = index_register;
+
+        let (cell_width, cell_height) = self.cell_size();
+        let bytes_per_sprite = self.config.bytes_per_sprite();
+
+        self.canvas.set_draw_color(BACKGROUND_COLOR);
+        self.canvas.clear();
+
+        let first_sprite = (self.first_visible_row * SPRITES_PER_ROW) as usize;
+        let visible_sprites = (VISIBLE_ROWS * SPRITES_PER_ROW) as usize;
+        let addresses = self.sprite_addresses();
+
+        for (i, address) in addresses
+            .iter()
+            .skip(first_sprite)
+            .take(visible_sprites)
+            .enumerate()
+        {
+            let cell_x = (i as u32 % SPRITES_PER_ROW * cell_width) as i32;
+            let cell_y = (i as u32 / SPRITES_PER_ROW * cell_height) as i32;
+            let cell = Rect::new(
+                cell_x,
+                cell_y,
+                cell_width + CELL_GAP,
+                cell_height + CELL_GAP,
+            );
+
+            let contains_index_register =
+                (*address..*address + bytes_per_sprite).contains(&index_register);
+            if contains_index_register {
+                self.canvas.set_draw_color(INDEX_REGISTER_COLOR);
+                self.canvas
+                    .fill_rect(cell)
+                    .expect("ERROR: Could not fill rectangle");
+            }
+            if self.selected_sprites.contains(address) {
+                self.canvas.set_draw_color(SELECTION_COLOR);
+                self.canvas
+                    .fill_rect(Rect::new(cell_x + 1, cell_y + 1, cell_width, cell_height))
+                    .expect("ERROR: Could not fill rectangle");
+            }
+
+            self.canvas.set_draw_color(Color::BLACK);
+            self.canvas
+                .fill_rect(Rect::new(
+                    cell_x + CELL_GAP as i32,
+                    cell_y + CELL_GAP as i32,
+                    cell_width - CELL_GAP,
+                    cell_height - CELL_GAP,
+                ))
+                .expect("ERROR: Could not fill rectangle");
+
+            self.canvas.set_draw_color(Color::WHITE);
+            for y in 0..self.config.sprite_height as u32 {
+                for x in 0..self.config.sprite_width() {
+                    if sprite_pixel(
+                        memory,
+                        *address as usize,
+                        self.config.bytes_per_row(),
+                        x as usize,
+                        y as usize,
+                    ) {
+                        self.canvas
+                            .fill_rect(Rect::new(
+                                cell_x + (CELL_GAP + x * VIEWER_SCALE_FACTOR) as i32,
+                                cell_y + (CELL_GAP + y * VIEWER_SCALE_FACTOR) as i32,
+                                VIEWER_SCALE_FACTOR,
+                                VIEWER_SCALE_FACTOR,
+                            ))
+                            .expect("ERROR: Could not fill rectangle");
+                    }
+                }
+            }
+        }
+
+        self.canvas.present();
+    }
+}
@@ -0,0 +1,435 @@
+use std::fmt::Write;
+
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::save_state::MachineState;
+use super::{decode, Address, KeyWait, MEMORY_SIZE, PROGRAM_OFFSET};
+
+// changed bytes closer than this are reported as one range
+const RANGE_MERGE_DISTANCE: usize = 4;
+// number of instructions disassembled before and after a changed range
+const DISASSEMBLY_CONTEXT: usize = 2;
+
+fn raw_instruction_at(state: &MachineState, address: Address) -> u16 {
+    let high = state.memory[address as usize % MEMORY_SIZE as usize] as u16;
+    let low = state.memory[(address as usize + 1) % MEMORY_SIZE as usize] as u16;
+    high << 8 | low
+}
+
+fn key_list(key_states: &[bool; 16]) -> String {
+    let keys: Vec<String> = (0..16)
+        .filter(|&key| key_states[key])
+        .map(|key| format!("{:X}", key))
+        .collect();
+    if keys.is_empty() {
+        "none".to_string()
+    } else {
+        keys.join(" ")
+    }
+}
+
+fn key_wait_description(key_wait: &Option<KeyWait>) -> String {
+    match key_wait {
+        None => "no".to_string(),
+        Some(KeyWait {
+            register,
+            pressed_key: None,
+        }) => format!("V{:X}", register),
+        Some(KeyWait {
+            register,
+            pressed_key: Some(key),
+        }) => format!("V{:X}, key {:X} pressed", register, key),
+    }
+}
+
+fn stack_list(stack: &[Address]) -> String {
+    if stack.is_empty() {
+        return "empty".to_string();
+    }
+    stack
+        .iter()
+        .map(|address| format!("{:#05x}", address))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn display_row(state: &MachineState, y: u32) -> String {
+    (0..DISPLAY_WIDTH)
+        .map(|x| {
+            if state.display_buffer[(y * DISPLAY_WIDTH + x) as usize] {
+                '#'
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+// the instructions around the program counter
+fn write_disassembly(report: &mut String, state: &MachineState) {
+    let start = state
+        .program_counter
+        .saturating_sub(2 * DISASSEMBLY_CONTEXT as u16)
+        .max(PROGRAM_OFFSET);
+    let end = (state.program_counter + 2 * (DISASSEMBLY_CONTEXT as u16 + 1)).min(MEMORY_SIZE - 1);
+
+    for address in (start..end).step_by(2) {
+        let marker = if address == state.program_counter {
+            ">"
+        } else {
+            " "
+        };
+        let instruction = raw_instruction_at(state, address);
+        writeln!(
+            report,
+            "  {} {:#05x}: {:04x}  {}",
+            marker,
+            address,
+            instruction,
+            decode(instruction)
+        )
+        .unwrap();
+    }
+}
+
+pub fn show_state(state: &MachineState) -> String {
+    let mut report = String::new();
+
+    writeln!(report, "ROM hash: {:016x}", state.rom_hash).unwrap();
+    writeln!(
+        report,
+        "PC: {:#05x}  I: {:#05x}  DT: {}  ST: {}",
+        state.program_counter, state.index_register, state.delay_timer, state.sound_timer
+    )
+    .unwrap();
+    for (row, registers) in state.data_registers.chunks(8).enumerate() {
+        let registers: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X}: {:#04x}", row * 8 + i, value))
+            .collect();
+        writeln!(report, "{}", registers.join("  ")).unwrap();
+    }
+    let rpl_flags: Vec<String> = state
+        .rpl_flags
+        .iter()
+        .map(|value| format!("{:02x}", value))
+        .collect();
+    writeln!(report, "RPL flags: {}", rpl_flags.join(" ")).unwrap();
+    writeln!(report, "Stack: {}", stack_list(&state.stack)).unwrap();
+    writeln!(report, "Pressed keys: {}", key_list(&state.key_states)).unwrap();
+    writeln!(
+        report,
+        "Waiting for key: {}",
+        key_wait_description(&state.key_wait)
+    )
+    .unwrap();
+    writeln!(report, "Random state: {:016x}", state.random_state).unwrap();
+
+    writeln!(report, "\nCode at PC:").unwrap();
+    write_disassembly(&mut report, state);
+
+    writeln!(report, "\nDisplay:").unwrap();
+    for y in 0..DISPLAY_HEIGHT {
+        writeln!(report, "{}", display_row(state, y)).unwrap();
+    }
+
+    report
+}
+
+// ranges of differing memory as (start, end) with an exclusive end
+fn changed_memory_ranges(a: &MachineState, b: &MachineState) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for address in (0..a.memory.len()).filter(|&address| a.memory[address] != b.memory[address]) {
+        match ranges.last_mut() {
+            Some((_, end)) if address - *end < RANGE_MERGE_DISTANCE => *end = address + 1,
+            _ => ranges.push((address, address + 1)),
+        }
+    }
+
+    ranges
+}
+
+fn write_register_diff(report: &mut String, a: &MachineState, b: &MachineState) {
+    let mut registers = vec![
+        ("PC", a.program_counter, b.program_counter),
+        ("I", a.index_register, b.index_register),
+        ("DT", a.delay_timer as u16, b.delay_timer as u16),
+        ("ST", a.sound_timer as u16, b.sound_timer as u16),
+    ];
+    let names: Vec<String> = (0..16).map(|i| format!("V{:X}", i)).collect();
+    for (i, name) in names.iter().enumerate() {
+        registers.push((name, a.data_registers[i] as u16, b.data_registers[i] as u16));
+    }
+    // the RPL user flags
+    let flag_names: Vec<String> = (0..16).map(|i| format!("R{:X}", i)).collect();
+    for (i, name) in flag_names.iter().enumerate() {
+        registers.push((name, a.rpl_flags[i] as u16, b.rpl_flags[i] as u16));
+    }
+
+    let changed: Vec<_> = registers.iter().filter(|(_, a, b)| a != b).collect();
+    if changed.is_empty()
+        && a.random_state == b.random_state
+        && a.key_states == b.key_states
+        && a.key_wait == b.key_wait
+    {
+        writeln!(report, "Registers: identical").unwrap();
+        return;
+    }
+
+    writeln!(report, "Registers:").unwrap();
+    for (name, a, b) in changed {
+        writeln!(report, "  {:<3} {:#06x} -> {:#06x}", name, a, b).unwrap();
+    }
+    if a.random_state != b.random_state {
+        writeln!(
+            report,
+            "  random state {:016x} -> {:016x}",
+            a.random_state, b.random_state
+        )
+        .unwrap();
+    }
+    if a.key_states != b.key_states {
+        writeln!(
+            report,
+            "  pressed keys {} -> {}",
+            key_list(&a.key_states),
+            key_list(&b.key_states)
+        )
+        .unwrap();
+    }
+    if a.key_wait != b.key_wait {
+        writeln!(
+            report,
+            "  waiting for key {} -> {}",
+            key_wait_description(&a.key_wait),
+            key_wait_description(&b.key_wait)
+        )
+        .unwrap();
+    }
+}
+
+fn write_stack_diff(report: &mut String, a: &MachineState, b: &MachineState) {
+    if a.stack == b.stack {
+        writeln!(report, "Stack: identical ({})", stack_list(&a.stack)).unwrap();
+        return;
+    }
+
+    writeln!(report, "Stack:").unwrap();
+    for level in 0..a.stack.len().max(b.stack.len()) {
+        let entry = |stack: &[Address]| match stack.get(level) {
+            Some(address) => format!("{:#05x}", address),
+            None => "-".to_string(),
+        };
+        let marker = if a.stack.get(level) != b.stack.get(level) {
+            "*"
+        } else {
+            " "
+        };
+        writeln!(
+            report,
+            "  {} {:>2}: {:>5} -> {:>5}",
+            marker,
+            level,
+            entry(&a.stack),
+            entry(&b.stack)
+        )
+        .unwrap();
+    }
+}
+
+// lists the changed bytes and, for ranges in the program area, the instructions around
+// them as seen in both states
+fn write_memory_diff(report: &mut String, a: &MachineState, b: &MachineState) {
+    let ranges = changed_memory_ranges(a, b);
+    if ranges.is_empty() {
+        writeln!(report, "Memory: identical").unwrap();
+        return;
+    }
+
+    writeln!(report, "Memory ({} changed ranges):", ranges.len()).unwrap();
+    for (start, end) in ranges {
+        writeln!(
+            report,
+            "  {:#05x}-{:#05x} ({} bytes)",
+            start,
+            end - 1,
+            end - start
+        )
+        .unwrap();
+        let bytes = |state: &MachineState| {
+            state.memory[start..end]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        writeln!(report, "    a: {}", bytes(a)).unwrap();
+        writeln!(report, "    b: {}", bytes(b)).unwrap();
+
+        if end <= PROGRAM_OFFSET as usize {
+            continue;
+        }
+
+        // instructions are assumed to be aligned to the start of the program
+        let first = start.max(PROGRAM_OFFSET as usize) & !1;
+        let context_start = first
+            .saturating_sub(2 * DISASSEMBLY_CONTEXT)
+            .max(PROGRAM_OFFSET as usize);
+        let context_end = (end + 2 * DISASSEMBLY_CONTEXT).min(MEMORY_SIZE as usize - 1);
+        for address in (context_start..context_end).step_by(2) {
+            let address = address as Address;
+            let instruction_a = raw_instruction_at(a, address);
+            let instruction_b = raw_instruction_at(b, address);
+            let marker = if instruction_a != instruction_b {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(
+                report,
+                "    {} {:#05x}: {:04x} {:<18} | {:04x} {}",
+                marker,
+                address,
+                instruction_a,
+                decode(instruction_a).to_string(),
+                instruction_b,
+                decode(instruction_b)
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn write_display_diff(report: &mut String, a: &MachineState, b: &MachineState) {
+    let changed_pixels = a
+        .display_buffer
+        .iter()
+        .zip(b.display_buffer.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    if changed_pixels == 0 {
+        writeln!(report, "Display: identical").unwrap();
+        return;
+    }
+
+    writeln!(report, "Display ({} pixels differ):", changed_pixels).unwrap();
+    writeln!(
+        report,
+        "  {:<width$}   b",
+        "a",
+        width = DISPLAY_WIDTH as usize
+    )
+    .unwrap();
+    for y in 0..DISPLAY_HEIGHT {
+        let marker = if display_row(a, y) != display_row(b, y) {
+            "*"
+        } else {
+            " "
+        };
+        writeln!(
+            report,
+            "{} {} | {}",
+            marker,
+            display_row(a, y),
+            display_row(b, y)
+        )
+        .unwrap();
+    }
+}
+
+pub fn diff_states(a: &MachineState, b: &MachineState) -> String {
+    let mut report = String::new();
+
+    if a.rom_hash != b.rom_hash {
+        writeln!(
+            report,
+            "WARNING: the states belong to different ROMs ({:016x} and {:016x})",
+            a.rom_hash, b.rom_hash
+        )
+        .unwrap();
+    }
+    write_register_diff(&mut report, a, b);
+    write_stack_diff(&mut report, a, b);
+    write_memory_diff(&mut report, a, b);
+    write_display_diff(&mut report, a, b);
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_state() -> MachineState {
+        MachineState {
+            rom_hash: 1,
+            data_registers: [0; 16],
+            memory: [0; MEMORY_SIZE as usize],
+            program_counter: PROGRAM_OFFSET,
+            index_register: 0,
+            stack: vec![0x202],
+            delay_timer: 0,
+            sound_timer: 0,
+            random_state: 0,
+            display_buffer: [false; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize],
+            key_states: [false; 16],
+            key_wait: None,
+            rpl_flags: [0; 16],
+        }
+    }
+
+    #[test]
+    fn merges_close_memory_changes() {
+        let a = blank_state();
+        let mut b = blank_state();
+        // gaps shorter than the merge distance are merged, the one before 0x307 is not
+        for address in [0x300, 0x302, 0x307, 0xfff] {
+            b.memory[address] = 1;
+        }
+
+        assert_eq!(
+            changed_memory_ranges(&a, &b),
+            vec![(0x300, 0x303), (0x307, 0x308), (0xfff, 0x1000)]
+        );
+        assert!(changed_memory_ranges(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn diffs_registers_and_stack() {
+        let a = blank_state();
+        let mut b = blank_state();
+        b.program_counter = 0x204;
+        b.data_registers[3] = 0x2a;
+        b.rpl_flags[0xf] = 1;
+        b.stack.push(0x210);
+
+        let report = diff_states(&a, &b);
+        assert!(report.contains("  PC  0x0200 -> 0x0204\n"));
+        assert!(report.contains("  V3  0x0000 -> 0x002a\n"));
+        assert!(report.contains("  RF  0x0000 -> 0x0001\n"));
+        assert!(!report.contains("V0 "));
+        assert!(report.contains("Stack:\n     0: 0x202 -> 0x202\n  *  1:     - -> 0x210\n"));
+        assert!(report.contains("Memory: identical"));
+        assert!(report.contains("Display: identical"));
+        assert!(!report.contains("WARNING"));
+
+        let report = diff_states(&a, &a);
+        assert!(report.contains("Registers: identical"));
+        assert!(report.contains("Stack: identical (0x202)"));
+    }
+
+    #[test]
+    fn shows_the_code_at_pc() {
+        let mut state = blank_state();
+        // LD V0, 0x2a; JP 0x200
+        state.memory[0x200..0x204].copy_from_slice(&[0x60, 0x2a, 0x12, 0x00]);
+        state.program_counter = 0x202;
+
+        let report = show_state(&state);
+        assert!(report.contains("PC: 0x202  I: 0x000  DT: 0  ST: 0\n"));
+        assert!(report.contains("    0x200: 602a  LD V0, 0x2a\n"));
+        assert!(report.contains("  > 0x202: 1200  JP 0x200\n"));
+        assert!(report.contains("Waiting for key: no\n"));
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+// the only platform emulated so far
+pub const PLATFORM: &str = "CHIP-8";
+
+// what the emulator is currently doing, e.g. for the window title. available to every
+// frontend through Chip8::status.
+#[derive(Clone, PartialEq)]
+pub struct Status {
+    // file name of the ROM without extension
+    pub rom_name: String,
+    pub platform: &'static str,
+    // measured over the last second, in percent of 60 frames per second
+    pub speed: f64,
+    pub instructions_per_second: u64,
+    pub paused: bool,
+    pub rewinding: bool,
+    // the CPU is halted at FX0A until a key is pressed and released
+    pub waiting_for_key: bool,
+}
+
+// e.g. "pong - CHIP-8 - 100% - Waiting for key (FX0A)"
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} - {:.0}%",
+            self.rom_name, self.platform, self.speed
+        )?;
+        if self.paused {
+            write!(f, " - Paused")?;
+        } else if self.rewinding {
+            write!(f, " - Rewinding")?;
+        } else if self.waiting_for_key {
+            write!(f, " - Waiting for key (FX0A)")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Read, Write};
+use std::time::{Duration, Instant};
+
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::palette::Palette;
+
+pub const DEFAULT_KEY_RELEASE_TIMEOUT: Duration = Duration::from_millis(150);
+
+const ESCAPE: u8 = 0x1b;
+const CTRL_C: u8 = 0x03;
+// number of messages shown below the display
+const MAX_MESSAGES: usize = 4;
+
+// the settings of the terminal before it was switched to raw mode
+#[cfg(unix)]
+type TerminalMode = libc::termios;
+#[cfg(not(unix))]
+type TerminalMode = ();
+
+pub struct TerminalConfig {
+    // the i-th character is the key for the CHIP-8 key i
+    pub keys: Vec<char>,
+    // terminals only report key presses. a key counts as held until no press (or
+    // auto-repeat) of it arrived for this long
+    pub key_release_timeout: Duration,
+}
+
+// draws the display into the terminal and reads the keys from stdin, without SDL.
+// two rows of pixels share one line of text: the upper half block is drawn in the color
+// of the upper pixel, its background in the color of the lower one.
+pub struct Terminal {
+    config: TerminalConfig,
+    // None until the terminal is switched to raw mode
+    original_mode: Option<TerminalMode>,
+    last_key_presses: [Option<Instant>; 16],
+    should_exit: bool,
+    was_beeping: bool,
+    // the newest messages of the emulator, drawn below the display
+    messages: VecDeque<String>,
+    messages_changed: bool,
+}
+
+fn color_code(palette: &Palette, is_set: bool) -> (u8, u8, u8) {
+    let color = palette.colors[is_set as usize];
+    (color.r, color.g, color.b)
+}
+
+impl Terminal {
+    pub fn init(config: TerminalConfig) -> Terminal {
+        Terminal {
+            config,
+            original_mode: None,
+            last_key_presses: [None; 16],
+            should_exit: false,
+            was_beeping: false,
+            messages: VecDeque::new(),
+            messages_changed: false,
+        }
+    }
+
+    // the terminal is only taken over once the emulation runs, so prompts before still work
+    fn start(&mut self) {
+        if self.original_mode.is_some() {
+            return;
+        }
+
+        let original_mode = enable_raw_mode();
+
+        // clear the screen and hide the cursor. the last frame stays visible on exit, with
+        // the messages of the emulator below it
+        print!("\x1b[2J\x1b[?25l");
+        std::io::stdout().flush().ok();
+
+        self.original_mode = Some(original_mode);
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.should_exit
+    }
+
+    pub fn key_states(&self) -> [bool; 16] {
+        let now = Instant::now();
+        let mut key_states = [false; 16];
+        for (is_pressed, last_press) in key_states.iter_mut().zip(self.last_key_presses.iter()) {
+            *is_pressed = last_press
+                .is_some_and(|last_press| now - last_press < self.config.key_release_timeout);
+        }
+        key_states
+    }
+
+    // reads all pending input. Escape or Ctrl+C quit, escape sequences (e.g. of the arrow
+    // keys) are ignored
+    pub fn process_input(&mut self) {
+        self.start();
+        let mut bytes = [0; 64];
+        let length = std::io::stdin().read(&mut bytes).unwrap_or(0);
+        let bytes = &bytes[..length];
+
+        if bytes == [ESCAPE] || bytes.contains(&CTRL_C) {
+            self.should_exit = true;
+            return;
+        }
+
+        let now = Instant::now();
+        let mut in_escape_sequence = false;
+        for &byte in bytes {
+            if byte == ESCAPE {
+                in_escape_sequence = true;
+                continue;
+            }
+            if in_escape_sequence {
+                // escape sequences end with a letter or ~
+                in_escape_sequence = !(byte.is_ascii_alphabetic() || byte == b'~');
+                continue;
+            }
+
+            let character = (byte as char).to_ascii_lowercase();
+            if let Some(key) = self.config.keys.iter().position(|&key| key == character) {
+                self.last_key_presses[key] = Some(now);
+            }
+        }
+    }
+
+    // sets the title of the terminal window (or tab)
+    pub fn set_title(&mut self, title: &str) {
+        // control characters would end the escape sequence early
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        print!("\x1b]0;{}\x07", title);
+    }
+
+    // shows the message below the display instead of printing it, which would scroll the
+    // display away
+    pub fn show_message(&mut self, message: String) {
+        self.messages.push_back(message);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages_changed = true;
+    }
+
+    // rings the terminal bell when the sound timer starts
+    pub fn update_beeper(&mut self, is_beeping: bool) {
+        if is_beeping && !self.was_beeping {
+            print!("\x07");
+        }
+        self.was_beeping = is_beeping;
+    }
+
+    pub fn draw(&mut self, display_buffer: &[bool], palette: &Palette, buffer_changed: bool) {
+        self.start();
+        if !buffer_changed && !self.messages_changed {
+            std::io::stdout().flush().ok();
+            return;
+        }
+
+        let mut output = String::new();
+        if buffer_changed {
+            output += &display_output(display_buffer, palette);
+        }
+
+        // the messages start in the line below the display, the rest of the screen is cleared
+        let message_row = DISPLAY_HEIGHT as usize / 2 + 1;
+        if self.messages_changed {
+            output += &format!("\x1b[{};1H", message_row);
+            for message in self.messages.iter() {
+                output += &format!("{}\x1b[K\r\n", message);
+            }
+            output += "\x1b[J";
+            self.messages_changed = false;
+        }
+        // anything printed later (e.g. on exit) goes below the messages
+        output += &format!("\x1b[{};1H", message_row + self.messages.len());
+
+        let mut stdout = std::io::stdout();
+        stdout.write_all(output.as_bytes()).ok();
+        stdout.flush().ok();
+    }
+}
+
+// the escape sequences drawing the display from the upper left corner
+fn display_output(display_buffer: &[bool], palette: &Palette) -> String {
+    let width = DISPLAY_WIDTH as usize;
+    let mut output = String::from("\x1b[H");
+    for row in 0..DISPLAY_HEIGHT as usize / 2 {
+        // colors are only sent when they differ from the previous cell
+        let mut colors = None;
+        for x in 0..width {
+            let upper = display_buffer[row * 2 * width + x];
+            let lower = display_buffer[(row * 2 + 1) * width + x];
+            if colors != Some((upper, lower)) {
+                let (r, g, b) = color_code(palette, upper);
+                let (br, bg, bb) = color_code(palette, lower);
+                output += &format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                    r, g, b, br, bg, bb
+                );
+                colors = Some((upper, lower));
+            }
+            output.push('\u{2580}');
+        }
+        output += "\x1b[0m\r\n";
+    }
+    output
+}
+
+// the terminal frontend needs raw mode, which is only switched on for a Unix terminal on stdin
+pub fn check_terminal() -> Result<(), &'static str> {
+    if !cfg!(unix) {
+        return Err("The terminal frontend is only available on Unix");
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err("The terminal frontend needs stdin to be a terminal");
+    }
+    Ok(())
+}
+
+// switches stdin to raw mode: no echo, no line buffering and reads that don't block.
+// returns the previous settings
+#[cfg(unix)]
+fn enable_raw_mode() -> TerminalMode {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    // SAFETY: tcgetattr fills the struct if it succeeds, which is checked. check_terminal
+    // made sure that stdin is a terminal
+    let original_mode = unsafe {
+        if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+            panic!("ERROR: Could not get the terminal settings. Exiting...");
+        }
+        termios.assume_init()
+    };
+    let mut raw = original_mode;
+    // SAFETY: raw is a valid termios struct
+    unsafe {
+        libc::cfmakeraw(&mut raw);
+        // output still translates \n to \r\n, so lines printed while the terminal is
+        // taken over (e.g. on exit) start at the beginning of the line
+        raw.c_oflag |= libc::OPOST | libc::ONLCR;
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+    }
+    original_mode
+}
+
+// check_terminal rejects the terminal frontend on other platforms
+#[cfg(not(unix))]
+fn enable_raw_mode() -> TerminalMode {}
+
+#[cfg(unix)]
+fn restore_mode(original_mode: &TerminalMode) {
+    // SAFETY: original_mode was filled by tcgetattr
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original_mode);
+    }
+}
+
+#[cfg(not(unix))]
+fn restore_mode(_original_mode: &TerminalMode) {}
+
+impl Drop for Terminal {
+    // restores the terminal as it was before
+    fn drop(&mut self) {
+        let Some(original_mode) = &self.original_mode else {
+            return;
+        };
+
+        print!("\x1b[0m\x1b[?25h");
+        std::io::stdout().flush().ok();
+        restore_mode(original_mode);
+    }
+}
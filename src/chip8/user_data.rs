@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a hash. for ROMs it is used to find the files belonging to a ROM and to make
+// sure they are not used with another one
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn user_data_directory(subdirectory: &str) -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("chip8")
+        .join(subdirectory)
+}
+
+pub fn state_slot_path(rom_hash: u64, slot: u8) -> PathBuf {
+    user_data_directory("states").join(format!("{:016x}.slot{}.state", rom_hash, slot))
+}
+
+pub fn resume_state_path(rom_hash: u64) -> PathBuf {
+    user_data_directory("states").join(format!("{:016x}.resume.state", rom_hash))
+}
+
+pub fn rpl_flags_path(rom_hash: u64) -> PathBuf {
+    user_data_directory("flags").join(format!("{:016x}.flags", rom_hash))
+}
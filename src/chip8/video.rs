@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::palette::Palette;
+
+const AUDIO_SAMPLE_RATE: u32 = 44100;
+const FRAMES_PER_SECOND: u32 = 60;
+const SAMPLES_PER_FRAME: u32 = AUDIO_SAMPLE_RATE / FRAMES_PER_SECOND;
+// the beeper, the same square wave as played by the sound system
+const BEEP_FREQUENCY: f32 = 440.0;
+const BEEP_VOLUME: f32 = 0.12;
+const WAV_HEADER_SIZE: u32 = 44;
+
+fn encoding_error(err: gif::EncodingError) -> std::io::Error {
+    std::io::Error::other(err.to_string())
+}
+
+// GIF delays are given in hundredths of a second, which 60 fps don't divide evenly.
+// the delay of a frame is rounded so the total time stays exact.
+fn centiseconds(frame: u64) -> u64 {
+    frame * 100 / FRAMES_PER_SECOND as u64
+}
+
+struct GifWriter {
+    encoder: gif::Encoder<BufWriter<File>>,
+    // identical frames are merged into one with a longer delay
+    pending_frame: Option<(Vec<u8>, u64)>,
+}
+
+// uncompressed video (YUV 4:4:4) with the audio as 16-bit mono PCM next to it
+struct RawWriter {
+    video: BufWriter<File>,
+    audio: BufWriter<File>,
+    audio_samples: u32,
+    phase: f32,
+    // Y, Cb and Cr of every palette color
+    yuv_colors: Vec<[u8; 3]>,
+}
+
+enum VideoWriter {
+    Gif(GifWriter),
+    Raw(RawWriter),
+}
+
+// records the display of every emulated frame, independent of the wall-clock. depending
+// on the extension of the path, an animated GIF or a Y4M-file plus a WAV-file with the
+// same name is written.
+pub struct VideoRecorder {
+    writer: VideoWriter,
+    scale: u32,
+    frame: u64,
+}
+
+fn rgb_to_yuv(r: u8, g: u8, b: u8) -> [u8; 3] {
+    // ITU-R BT.601, limited range
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+fn write_wav_header<W: Write>(writer: &mut W, samples: u32) -> std::io::Result<()> {
+    let data_size = samples * 2;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM, 1 channel
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&AUDIO_SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(AUDIO_SAMPLE_RATE * 2).to_le_bytes())?;
+    // bytes per sample, bits per sample
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+impl VideoRecorder {
+    pub fn create(path: &Path, palette: &Palette, scale: u32) -> std::io::Result<VideoRecorder> {
+        let width = DISPLAY_WIDTH * scale;
+        let height = DISPLAY_HEIGHT * scale;
+
+        let is_gif = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        let writer = if is_gif {
+            let global_palette: Vec<u8> = palette
+                .colors
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect();
+            let mut encoder = gif::Encoder::new(
+                BufWriter::new(File::create(path)?),
+                width as u16,
+                height as u16,
+                &global_palette,
+            )
+            .map_err(encoding_error)?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(encoding_error)?;
+
+            VideoWriter::Gif(GifWriter {
+                encoder,
+                pending_frame: None,
+            })
+        } else {
+            let mut video = BufWriter::new(File::create(path)?);
+            writeln!(
+                video,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, FRAMES_PER_SECOND
+            )?;
+            let mut audio = BufWriter::new(File::create(path.with_extension("wav"))?);
+            // the sizes are filled in when the recording is finished
+            write_wav_header(&mut audio, 0)?;
+
+            VideoWriter::Raw(RawWriter {
+                video,
+                audio,
+                audio_samples: 0,
+                phase: 0.0,
+                yuv_colors: palette
+                    .colors
+                    .iter()
+                    .map(|color| rgb_to_yuv(color.r, color.g, color.b))
+                    .collect(),
+            })
+        };
+
+        Ok(VideoRecorder {
+            writer,
+            scale,
+            frame: 0,
+        })
+    }
+
+    // the palette indices of the scaled display, row by row
+    fn scaled_indices(&self, display_buffer: &[bool]) -> Vec<u8> {
+        let width = (DISPLAY_WIDTH * self.scale) as usize;
+        let height = (DISPLAY_HEIGHT * self.scale) as usize;
+        let scale = self.scale as usize;
+
+        (0..width * height)
+            .map(|i| {
+                let x = i % width / scale;
+                let y = i / width / scale;
+                display_buffer[y * DISPLAY_WIDTH as usize + x] as u8
+            })
+            .collect()
+    }
+
+    // is_beeping is whether the sound timer was active during the frame
+    pub fn record_frame(
+        &mut self,
+        display_buffer: &[bool],
+        is_beeping: bool,
+    ) -> std::io::Result<()> {
+        let indices = self.scaled_indices(display_buffer);
+        let frame = self.frame;
+        self.frame += 1;
+
+        match &mut self.writer {
+            VideoWriter::Gif(writer) => {
+                if writer
+                    .pending_frame
+                    .as_ref()
+                    .is_some_and(|(pending, _)| *pending == indices)
+                {
+                    return Ok(());
+                }
+                if let Some((pending, start)) = writer.pending_frame.replace((indices, frame)) {
+                    write_gif_frame(&mut writer.encoder, self.scale, &pending, start, frame)?;
+                }
+            }
+            VideoWriter::Raw(writer) => {
+                writer.video.write_all(b"FRAME\n")?;
+                for plane in 0..3 {
+                    let plane: Vec<u8> = indices
+                        .iter()
+                        .map(|&index| writer.yuv_colors[index as usize][plane])
+                        .collect();
+                    writer.video.write_all(&plane)?;
+                }
+
+                let phase_increment = BEEP_FREQUENCY / AUDIO_SAMPLE_RATE as f32;
+                for _ in 0..SAMPLES_PER_FRAME {
+                    let sample = if !is_beeping {
+                        0.0
+                    } else if writer.phase <= 0.5 {
+                        BEEP_VOLUME
+                    } else {
+                        -BEEP_VOLUME
+                    };
+                    writer
+                        .audio
+                        .write_all(&((sample * i16::MAX as f32) as i16).to_le_bytes())?;
+                    writer.phase = (writer.phase + phase_increment) % 1.0;
+                }
+                writer.audio_samples += SAMPLES_PER_FRAME;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    // writes the last frame and the sizes in the WAV header
+    pub fn finish(self) -> std::io::Result<()> {
+        match self.writer {
+            VideoWriter::Gif(mut writer) => {
+                if let Some((pending, start)) = writer.pending_frame.take() {
+                    write_gif_frame(&mut writer.encoder, self.scale, &pending, start, self.frame)?;
+                }
+                Ok(())
+            }
+            VideoWriter::Raw(mut writer) => {
+                writer.video.flush()?;
+                writer.audio.seek(SeekFrom::Start(0))?;
+                write_wav_header(&mut writer.audio, writer.audio_samples)?;
+                writer.audio.flush()
+            }
+        }
+    }
+}
+
+// writes the frame shown from frame start up to (excluding) frame end
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    scale: u32,
+    indices: &[u8],
+    start: u64,
+    end: u64,
+) -> std::io::Result<()> {
+    let mut frame = gif::Frame::from_indexed_pixels(
+        (DISPLAY_WIDTH * scale) as u16,
+        (DISPLAY_HEIGHT * scale) as u16,
+        indices,
+        None,
+    );
+    frame.delay = (centiseconds(end) - centiseconds(start)).min(u16::MAX as u64) as u16;
+    encoder.write_frame(&frame).map_err(encoding_error)
+}
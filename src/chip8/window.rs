@@ -0,0 +1,431 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureAccess};
+use sdl2::video::FullscreenType;
+
+use super::crt_filter::{apply_filters, scale_image, CrtFilter};
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::hud::{glyph_pixel, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::keypad_panel::{key_rect, KeypadArea, KEYPAD_LAYOUT, KEYPAD_PANEL_WIDTH};
+use super::palette::Palette;
+use super::persistence::{Persistence, PersistenceFilter};
+
+pub const DEFAULT_SCALE_FACTOR: u32 = 10;
+// limits the size of the image the CRT filters are applied to
+const MAX_FILTER_SCALE: u32 = 10;
+// the HUD text is scaled with the window, one font pixel per this many output pixels of
+// the window height
+const HUD_PIXELS_PER_FONT_PIXEL: u32 = 160;
+const RELEASED_KEY_COLOR: Color = Color::RGB(48, 48, 48);
+const RELEASED_LABEL_COLOR: Color = Color::RGB(200, 200, 200);
+
+// the lit pixels of a glyph of the HUD font with its upper left corner at (x, y), each
+// pixel_size wide and high
+fn glyph_rects(character: char, x: i32, y: i32, pixel_size: u32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for font_y in 0..GLYPH_HEIGHT {
+        for font_x in 0..GLYPH_WIDTH {
+            if glyph_pixel(character, font_x, font_y) {
+                rects.push(Rect::new(
+                    x + (font_x * pixel_size) as i32,
+                    y + (font_y * pixel_size) as i32,
+                    pixel_size,
+                    pixel_size,
+                ));
+            }
+        }
+    }
+    rects
+}
+
+// the SDL window showing the display
+pub struct Window {
+    canvas: sdl2::render::Canvas<sdl2::video::Window>,
+    // the display buffer is uploaded to this texture (one texel per pixel), which the
+    // renderer scales to the window
+    texture: Texture,
+    // only scale by whole numbers, so all CHIP-8 pixels have the same size
+    integer_scaling: bool,
+    persistence: PersistenceFilter,
+    filters: Vec<CrtFilter>,
+    // texture of the scaled and filtered image, with the scale it was created for
+    filter_texture: Option<(Texture, u32)>,
+    // shows a clickable hex keypad right of the display
+    keypad_panel: bool,
+}
+
+impl Window {
+    pub fn init(sdl_context: &sdl2::Sdl) -> Window {
+        let video_subsystem = sdl_context
+            .video()
+            .expect("ERROR: Could not initialize video-subsystem. Exiting...");
+
+        let window = video_subsystem
+            .window(
+                super::status::PLATFORM,
+                DISPLAY_WIDTH * DEFAULT_SCALE_FACTOR,
+                DISPLAY_HEIGHT * DEFAULT_SCALE_FACTOR,
+            )
+            .position_centered()
+            .resizable()
+            .allow_highdpi()
+            .build()
+            .expect("ERROR: Unable to initialize SDL2 video-subsystem. Exiting...");
+
+        let mut canvas = window
+            .into_canvas()
+            .software()
+            .build()
+            .expect("ERROR: Unable to create canvas in SDL2-window. Exiting...");
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+        canvas.present();
+
+        let texture = canvas
+            .texture_creator()
+            .create_texture(
+                PixelFormatEnum::RGB24,
+                TextureAccess::Streaming,
+                DISPLAY_WIDTH,
+                DISPLAY_HEIGHT,
+            )
+            .expect("ERROR: Unable to create texture for the display. Exiting...");
+
+        Window {
+            canvas,
+            texture,
+            integer_scaling: false,
+            persistence: PersistenceFilter::init(Persistence::Off),
+            filters: Vec::new(),
+            filter_texture: None,
+            keypad_panel: false,
+        }
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    // the whole-numbered scale of the display in the window
+    pub fn window_scale(&self) -> u32 {
+        (self.target_rect().width() / DISPLAY_WIDTH).max(1)
+    }
+
+    pub fn set_scale(&mut self, scale: u32) {
+        let (width, height) = self.content_size();
+        let window = self.canvas.window_mut();
+        window
+            .set_size(width * scale, height * scale)
+            .expect("ERROR: Could not resize window. Exiting...");
+        window.set_position(
+            sdl2::video::WindowPos::Centered,
+            sdl2::video::WindowPos::Centered,
+        );
+    }
+
+    pub fn set_persistence(&mut self, mode: Persistence) {
+        self.persistence = PersistenceFilter::init(mode);
+    }
+
+    pub fn set_filters(&mut self, filters: Vec<CrtFilter>) {
+        self.filters = filters;
+    }
+
+    pub fn set_integer_scaling(&mut self, integer_scaling: bool) {
+        self.integer_scaling = integer_scaling;
+    }
+
+    pub fn set_keypad_panel(&mut self, keypad_panel: bool) {
+        self.keypad_panel = keypad_panel;
+    }
+
+    // the size of the display and the keypad panel (if shown) in CHIP-8 pixels
+    fn content_size(&self) -> (u32, u32) {
+        if self.keypad_panel {
+            (DISPLAY_WIDTH + KEYPAD_PANEL_WIDTH, DISPLAY_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        }
+    }
+
+    // switches between windowed and borderless fullscreen at desktop resolution
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let state = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(err) = window.set_fullscreen(state) {
+            eprintln!("ERROR: Could not toggle fullscreen: {}", err);
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        if let Err(err) = self.canvas.window_mut().set_title(title) {
+            eprintln!("ERROR: Could not set window title: {}", err);
+        }
+    }
+
+    // the largest area keeping the aspect ratio of the display (and the keypad panel) that
+    // fits into the output, centered. the output size is in physical pixels, so high-DPI
+    // screens are used fully.
+    fn content_rect(&self) -> Rect {
+        let (output_width, output_height) = self
+            .canvas
+            .output_size()
+            .expect("ERROR: Could not get size of the window. Exiting...");
+        let (content_width, content_height) = self.content_size();
+
+        let (width, height) = if self.integer_scaling {
+            let scale = (output_width / content_width)
+                .min(output_height / content_height)
+                .max(1);
+            (content_width * scale, content_height * scale)
+        } else if output_width * content_height > output_height * content_width {
+            (
+                output_height * content_width / content_height,
+                output_height,
+            )
+        } else {
+            (output_width, output_width * content_height / content_width)
+        };
+
+        Rect::new(
+            (output_width as i32 - width as i32) / 2,
+            (output_height as i32 - height as i32) / 2,
+            width,
+            height,
+        )
+    }
+
+    // the area of the display, the left part of the content
+    fn target_rect(&self) -> Rect {
+        let content = self.content_rect();
+        let (content_width, _) = self.content_size();
+        Rect::new(
+            content.x(),
+            content.y(),
+            content.width() * DISPLAY_WIDTH / content_width,
+            content.height(),
+        )
+    }
+
+    // the area of the keypad panel right of the display, in output pixels
+    fn keypad_rect(&self) -> Rect {
+        let content = self.content_rect();
+        let display = self.target_rect();
+        Rect::new(
+            display.right(),
+            content.y(),
+            content.width() - display.width(),
+            content.height(),
+        )
+    }
+
+    // the keypad panel in window coordinates, None if it isn't shown
+    pub fn keypad_area(&self) -> Option<KeypadArea> {
+        if !self.keypad_panel {
+            return None;
+        }
+
+        let (output_width, output_height) = self
+            .canvas
+            .output_size()
+            .expect("ERROR: Could not get size of the window. Exiting...");
+        let (window_width, window_height) = self.canvas.window().size();
+        let panel = self.keypad_rect();
+        let to_window_x = |x: i32| (x as i64 * window_width as i64 / output_width as i64) as i32;
+        let to_window_y = |y: i32| (y as i64 * window_height as i64 / output_height as i64) as i32;
+        let (left, top) = (to_window_x(panel.left()), to_window_y(panel.top()));
+
+        Some(KeypadArea {
+            panel: Rect::new(
+                left,
+                top,
+                (to_window_x(panel.right()) - left).max(1) as u32,
+                (to_window_y(panel.bottom()) - top).max(1) as u32,
+            ),
+            window_width,
+            window_height,
+        })
+    }
+
+    // the keys in the layout of the COSMAC VIP, pressed ones in the foreground color
+    fn draw_keypad(&mut self, palette: &Palette, key_states: &[bool; 16]) {
+        let panel = self.keypad_rect();
+        let [background, foreground, ..] = palette.colors;
+
+        for (index, &key) in KEYPAD_LAYOUT.iter().enumerate() {
+            let rect = key_rect(panel, index);
+            let is_pressed = key_states[key as usize];
+            let (key_color, label_color) = if is_pressed {
+                (foreground, background)
+            } else {
+                (RELEASED_KEY_COLOR, RELEASED_LABEL_COLOR)
+            };
+
+            self.canvas.set_draw_color(key_color);
+            self.canvas
+                .fill_rect(rect)
+                .expect("ERROR: Could not draw keypad. Exiting...");
+
+            // the label takes up half of the height of the key
+            let pixel = (rect.height() / 2 / GLYPH_HEIGHT).max(1);
+            let label_x = rect.x() + (rect.width() as i32 - (GLYPH_WIDTH * pixel) as i32) / 2;
+            let label_y = rect.y() + (rect.height() as i32 - (GLYPH_HEIGHT * pixel) as i32) / 2;
+            let character = char::from_digit(key as u32, 16).unwrap();
+            let pixels = glyph_rects(character, label_x, label_y, pixel);
+            self.canvas.set_draw_color(label_color);
+            self.canvas
+                .fill_rects(&pixels)
+                .expect("ERROR: Could not draw keypad. Exiting...");
+        }
+    }
+
+    // converts the display buffer to an RGB image with the palette and the persistence
+    // applied. this advances the persistence history, so it is called once per output frame.
+    fn output_image(&mut self, display_buffer: &[bool], palette: &Palette) -> Vec<u8> {
+        let intensities = self.persistence.apply(display_buffer);
+        let [background, foreground, ..] = palette.colors;
+        let blend = |background: u8, foreground: u8, intensity: f32| {
+            (background as f32 + (foreground as f32 - background as f32) * intensity).round() as u8
+        };
+
+        intensities
+            .iter()
+            .flat_map(|&intensity| {
+                [
+                    blend(background.r, foreground.r, intensity),
+                    blend(background.g, foreground.g, intensity),
+                    blend(background.b, foreground.b, intensity),
+                ]
+            })
+            .collect()
+    }
+
+    fn upload_filtered_image(&mut self, image: &[u8], scale: u32) {
+        let width = DISPLAY_WIDTH * scale;
+        let height = DISPLAY_HEIGHT * scale;
+        let mut scaled = scale_image(
+            image,
+            DISPLAY_WIDTH as usize,
+            DISPLAY_HEIGHT as usize,
+            scale as usize,
+        );
+        apply_filters(
+            &self.filters,
+            &mut scaled,
+            width as usize,
+            height as usize,
+            scale as usize,
+        );
+
+        if self.filter_texture.as_ref().map(|(_, s)| *s) != Some(scale) {
+            let texture = self
+                .canvas
+                .texture_creator()
+                .create_texture(
+                    PixelFormatEnum::RGB24,
+                    TextureAccess::Streaming,
+                    width,
+                    height,
+                )
+                .expect("ERROR: Unable to create texture for the filtered display. Exiting...");
+            if let Some((old_texture, _)) = self.filter_texture.replace((texture, scale)) {
+                // SAFETY: the canvas that created the texture is still alive
+                unsafe { old_texture.destroy() };
+            }
+        }
+
+        let (texture, _) = self.filter_texture.as_mut().unwrap();
+        texture
+            .update(None, &scaled, width as usize * 3)
+            .expect("ERROR: Could not update filtered display texture. Exiting...");
+    }
+
+    // draws the lines of text on a translucent background, starting at the upper left corner
+    // or, from the bottom up, at the lower left corner
+    fn draw_text(&mut self, lines: &[String], from_bottom: bool) {
+        let (_, output_height) = self
+            .canvas
+            .output_size()
+            .expect("ERROR: Could not get size of the window. Exiting...");
+        let pixel = (output_height / HUD_PIXELS_PER_FONT_PIXEL).max(1);
+        let line_height = (GLYPH_HEIGHT + 2) * pixel;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        for (i, line) in lines.iter().enumerate() {
+            let y = if from_bottom {
+                output_height as i32 - ((lines.len() - i) as u32 * line_height) as i32
+            } else {
+                (i as u32 * line_height) as i32
+            };
+            let width = (line.chars().count() as u32 * (GLYPH_WIDTH + 1) + 1) * pixel;
+
+            self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            self.canvas
+                .fill_rect(Rect::new(0, y, width, line_height))
+                .expect("ERROR: Could not draw HUD. Exiting...");
+
+            let mut pixels = Vec::new();
+            for (column, character) in line.chars().enumerate() {
+                let glyph_x = ((column as u32 * (GLYPH_WIDTH + 1) + 1) * pixel) as i32;
+                pixels.extend(glyph_rects(character, glyph_x, y + pixel as i32, pixel));
+            }
+            self.canvas.set_draw_color(Color::WHITE);
+            self.canvas
+                .fill_rects(&pixels)
+                .expect("ERROR: Could not draw HUD. Exiting...");
+        }
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    // buffer_changed tells whether display_buffer or the palette changed since the last call.
+    // the status lines and messages of the HUD are drawn on top of the display.
+    pub fn draw(
+        &mut self,
+        display_buffer: &[bool],
+        palette: &Palette,
+        buffer_changed: bool,
+        key_states: &[bool; 16],
+        status_lines: &[String],
+        message_lines: &[String],
+    ) {
+        // the letterbox bars are drawn in black to set the display area apart
+        let target = self.target_rect();
+
+        // without filters, the renderer does the scaling. filters need the scaled image,
+        // which is rendered at the largest whole-numbered scale fitting the window.
+        let filter_scale = (target.width() / DISPLAY_WIDTH).clamp(1, MAX_FILTER_SCALE);
+        let scale_changed = self.filter_texture.as_ref().map(|(_, s)| *s) != Some(filter_scale);
+        let filtered = !self.filters.is_empty();
+
+        if buffer_changed || self.persistence.is_changing() || (filtered && scale_changed) {
+            let image = self.output_image(display_buffer, palette);
+            if filtered {
+                self.upload_filtered_image(&image, filter_scale);
+            } else {
+                self.texture
+                    .update(None, &image, DISPLAY_WIDTH as usize * 3)
+                    .expect("ERROR: Could not update display texture. Exiting...");
+            }
+        }
+
+        let texture = match &self.filter_texture {
+            Some((texture, _)) if filtered => texture,
+            _ => &self.texture,
+        };
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        self.canvas
+            .copy(texture, None, target)
+            .expect("ERROR: Could not draw display texture. Exiting...");
+        if self.keypad_panel {
+            self.draw_keypad(palette, key_states);
+        }
+        self.draw_text(status_lines, false);
+        self.draw_text(message_lines, true);
+        self.canvas.present();
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+// settings read from the config file. every setting is optional and can be overridden by
+// the corresponding command line argument.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub theme: Option<String>,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    // all 4 colors (background, plane 1, plane 2, both planes), overrides the theme
+    pub palette: Option<Vec<String>>,
+    pub scale: Option<u32>,
+    pub integer_scaling: Option<bool>,
+    pub hud: Option<bool>,
+    pub keypad: Option<bool>,
+    // false neither suspends sessions nor offers to resume them, like --no-resume
+    pub resume: Option<bool>,
+    pub frontend: Option<String>,
+    pub keymap: Option<String>,
+    // the host keys of single CHIP-8 keys (0 to F), replacing those of the keymap
+    pub keys: Option<BTreeMap<String, Vec<String>>>,
+    pub controller: ControllerConfig,
+    pub terminal_keys: Option<String>,
+    pub key_release_timeout: Option<u64>,
+}
+
+// the CHIP-8 keys (0 to F) of game controller inputs, e.g. a = "5" or "leftx-" = "4"
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControllerConfig {
+    pub axis_threshold: Option<f32>,
+    pub buttons: BTreeMap<String, String>,
+    // settings for single ROMs by their file name without extension, taking precedence
+    pub roms: BTreeMap<String, RomControllerConfig>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomControllerConfig {
+    pub axis_threshold: Option<f32>,
+    pub buttons: BTreeMap<String, String>,
+}
+
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("chip8")
+        .join("config.toml")
+}
+
+impl Config {
+    // a missing config file is not an error, an invalid one is
+    pub fn read_from_file(path: &Path) -> Result<Config, String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
@@ -1,16 +1,265 @@
+use std::io::Write;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use clap::{App, Arg};
-
-// TODO: fix timing-stuff in main: both emulated cycles and screen refreshrates
-// TODO: are not where they should be! (maybe completely different approach?)
+use clap::{App, Arg, SubCommand};
 
 mod chip8;
+mod config;
 
 const DEFAULT_FREQUENCY: &str = "700";
+const DEFAULT_REWIND_SECONDS: &str = "10";
+const DEFAULT_REWIND_GRANULARITY: &str = "1";
+
+const FRAMES_PER_SECOND: u64 = 60;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+const DEFAULT_SPRITE_RANGE: &str = "0x200-0x1000";
+const DEFAULT_SPRITE_HEIGHT: &str = "8";
+const DEFAULT_PHOSPHOR_DECAY: &str = "0.5";
+
+struct Arguments {
+    path: String,
+    frequency: u32,
+    sprite_viewer: Option<chip8::SpriteViewerConfig>,
+    code_writes: Option<chip8::CodeWriteAction>,
+    strict: bool,
+    load_state: Option<String>,
+    rewind_seconds: u32,
+    rewind_granularity: u32,
+    record: Option<String>,
+    play: Option<String>,
+    resume: bool,
+    clear_resume: bool,
+    palette: chip8::Palette,
+    scale: u32,
+    integer_scaling: bool,
+    hud: bool,
+    keypad: bool,
+    persistence: chip8::Persistence,
+    filters: Vec<chip8::CrtFilter>,
+    record_video: Option<String>,
+    input_script: Option<String>,
+    frontend: chip8::FrontendConfig,
+    keymap: chip8::Keymap,
+    controller_mapping: chip8::ControllerMapping,
+}
+
+enum Command {
+    Run(Box<Arguments>),
+    ControlFlowGraph {
+        path: String,
+        output: Option<String>,
+    },
+    ShowState {
+        path: String,
+    },
+    DiffStates {
+        a: String,
+        b: String,
+    },
+}
+
+fn parse_address(address: &str) -> Option<u16> {
+    let address = address.trim();
+    match address.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => address.parse::<u16>().ok(),
+    }
+}
+
+fn parse_sprite_viewer_config(range: &str, height: &str) -> chip8::SpriteViewerConfig {
+    let (start_address, end_address) = range
+        .split_once('-')
+        .and_then(|(start, end)| Some((parse_address(start)?, parse_address(end)?)))
+        .filter(|(start, end)| start < end && *end <= chip8::MEMORY_SIZE)
+        .expect("ERROR: Could not parse given sprite-range (expected START-END). Exiting...");
+    let sprite_height = height
+        .parse::<u8>()
+        .ok()
+        .filter(|height| (1..=16).contains(height))
+        .expect("ERROR: Sprite-height must be between 1 and 16. Exiting...");
+
+    chip8::SpriteViewerConfig {
+        start_address,
+        end_address,
+        sprite_height,
+    }
+}
+
+fn parse_color(color: &str) -> sdl2::pixels::Color {
+    chip8::parse_color(color).unwrap_or_else(|| {
+        eprintln!(
+            "ERROR: Could not parse color {} (expected RRGGBB). Exiting...",
+            color
+        );
+        std::process::exit(1);
+    })
+}
+
+// the theme is the base, colors given explicitly override it. command line arguments
+// take precedence over the config file.
+fn build_palette(matches: &clap::ArgMatches, config: &config::Config) -> chip8::Palette {
+    let theme = matches
+        .value_of("theme")
+        .or(config.theme.as_deref())
+        .unwrap_or("classic");
+    let mut palette = chip8::Palette::theme(theme).unwrap_or_else(|| {
+        eprintln!(
+            "ERROR: Unknown theme {} (available: {}). Exiting...",
+            theme,
+            chip8::THEME_NAMES.join(", ")
+        );
+        std::process::exit(1);
+    });
+
+    if let Some(colors) = &config.palette {
+        if colors.len() != palette.colors.len() {
+            eprintln!("ERROR: The palette in the config file must have 4 colors. Exiting...");
+            std::process::exit(1);
+        }
+        for (i, color) in colors.iter().enumerate() {
+            palette.colors[i] = parse_color(color);
+        }
+    }
+    if let Some(color) = matches
+        .value_of("background")
+        .or(config.background.as_deref())
+    {
+        palette.colors[0] = parse_color(color);
+    }
+    if let Some(color) = matches
+        .value_of("foreground")
+        .or(config.foreground.as_deref())
+    {
+        palette.colors[1] = parse_color(color);
+    }
+
+    palette
+}
+
+fn keymap_name<'a>(matches: &'a clap::ArgMatches, config: &'a config::Config) -> &'a str {
+    matches
+        .value_of("keymap")
+        .or(config.keymap.as_deref())
+        .unwrap_or("physical")
+}
+
+// the preset with the keys of the config file replacing its keys
+fn build_keymap(matches: &clap::ArgMatches, config: &config::Config) -> chip8::Keymap {
+    let name = keymap_name(matches, config);
+    let mut keymap = chip8::Keymap::preset(name).unwrap_or_else(|| {
+        eprintln!(
+            "ERROR: Unknown keymap {} (available: {}). Exiting...",
+            name,
+            chip8::KEYMAP_NAMES.join(", ")
+        );
+        std::process::exit(1);
+    });
+
+    for (key, host_keys) in config.keys.iter().flatten() {
+        let key = parse_chip8_key(key);
+        let host_keys = host_keys
+            .iter()
+            .map(|name| {
+                chip8::HostKey::from_name(name).unwrap_or_else(|| {
+                    eprintln!("ERROR: Unknown key {} in the config file. Exiting...", name);
+                    std::process::exit(1);
+                })
+            })
+            .collect();
+        keymap.bind(key, host_keys);
+    }
+
+    keymap
+}
+
+fn parse_chip8_key(key: &str) -> u8 {
+    match u8::from_str_radix(key, 16) {
+        Ok(key) if key < 16 => key,
+        _ => {
+            eprintln!(
+                "ERROR: Invalid CHIP-8 key {} in the config file (expected 0 to F). Exiting...",
+                key
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// the default mapping with the buttons of the config file and then those of the ROM
+fn build_controller_mapping(config: &config::Config, path: &str) -> chip8::ControllerMapping {
+    let rom_name = std::path::Path::new(path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let rom_config = config.controller.roms.get(&rom_name);
+
+    let mut mapping = chip8::ControllerMapping::default();
+    let buttons = config.controller.buttons.iter().chain(
+        rom_config
+            .iter()
+            .flat_map(|rom_config| rom_config.buttons.iter()),
+    );
+    for (input, key) in buttons {
+        let input = chip8::ControllerInput::from_name(input).unwrap_or_else(|| {
+            eprintln!(
+                "ERROR: Unknown controller input {} in the config file. Exiting...",
+                input
+            );
+            std::process::exit(1);
+        });
+        mapping.bind(input, parse_chip8_key(key));
+    }
+
+    if let Some(axis_threshold) = rom_config
+        .and_then(|rom_config| rom_config.axis_threshold)
+        .or(config.controller.axis_threshold)
+    {
+        mapping.set_axis_threshold(axis_threshold);
+    }
+
+    mapping
+}
+
+fn parse_terminal_config(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+) -> chip8::TerminalConfig {
+    if let Err(err) = chip8::check_terminal() {
+        eprintln!("ERROR: {}. Exiting...", err);
+        std::process::exit(1);
+    }
+
+    // without terminal-keys, the characters of the keymap preset are used
+    let keys: Vec<char> = matches
+        .value_of("terminal-keys")
+        .or(config.terminal_keys.as_deref())
+        .unwrap_or(chip8::Keymap::terminal_keys(keymap_name(matches, config)))
+        .to_lowercase()
+        .chars()
+        .collect();
+    if keys.len() != 16 {
+        eprintln!("ERROR: Terminal-keys must be exactly 16 characters. Exiting...");
+        std::process::exit(1);
+    }
+
+    let key_release_timeout = match matches.value_of("key-release-timeout") {
+        Some(timeout) => timeout
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .expect("ERROR: Could not parse given key-release-timeout to integer. Exiting..."),
+        None => config
+            .key_release_timeout
+            .map_or(chip8::DEFAULT_KEY_RELEASE_TIMEOUT, Duration::from_millis),
+    };
+
+    chip8::TerminalConfig {
+        keys,
+        key_release_timeout,
+    }
+}
 
-fn parse_command_arguments() -> (String, u32) {
+fn parse_command_arguments() -> Command {
     let matches = App::new("CHIP-8 Emulator")
         .version("0.0.1")
         .author("Felix Röpke")
@@ -29,8 +278,271 @@ fn parse_command_arguments() -> (String, u32) {
                 .help("The number of CHIP-8 instructions per second")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("sprite-viewer")
+                .long("sprite-viewer")
+                .help("Opens a window showing memory as sprites (F: follow I, E: export PNG)"),
+        )
+        .arg(
+            Arg::with_name("sprite-range")
+                .long("sprite-range")
+                .help("Memory range shown in the sprite-viewer, e.g. 0x200-0x400")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sprite-height")
+                .long("sprite-height")
+                .help("Height of the sprites in the sprite-viewer (1-15: 8xN, 16: 16x16)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("code-writes")
+                .long("code-writes")
+                .help("Warns or breaks (P resumes) when FX55/FX33 write into executed code or below 0x200")
+                .possible_values(&["warn", "break"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Reports undefined behavior and portability hazards of the ROM"),
+        )
+        .arg(
+            Arg::with_name("load-state")
+                .long("load-state")
+                .help("Path to a save state to start from (F1-F10 save, Shift+F1-F10 load slots)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rewind-seconds")
+                .long("rewind-seconds")
+                .help("How many seconds can be rewound by holding Backspace (0 disables rewinding)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rewind-granularity")
+                .long("rewind-granularity")
+                .help("Number of frames between two snapshots of the rewind buffer")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .help("Records the keypad input of every frame into the given movie file")
+                .conflicts_with_all(&["play", "load-state"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("play")
+                .long("play")
+                .help("Plays back a movie recorded with --record and verifies the final state")
+                .conflicts_with("load-state")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-resume")
+                .long("no-resume")
+                .help("Neither suspends the session on exit nor offers to resume a suspended one"),
+        )
+        .arg(
+            Arg::with_name("clear-resume")
+                .long("clear-resume")
+                .help("Deletes the suspended session of the ROM before starting"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .help("Path to the config file (default: config.toml in the user config directory)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .help("Color theme of the display")
+                .possible_values(&chip8::THEME_NAMES)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("foreground")
+                .long("foreground")
+                .help("Color of set pixels as RRGGBB, e.g. 33ff66")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("background")
+                .long("background")
+                .help("Color of unset pixels as RRGGBB, e.g. 000000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .long("scale")
+                .help("Initial size of a CHIP-8 pixel in the window (the window can be resized)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("integer-scaling")
+                .long("integer-scaling")
+                .help("Only scales the display by whole numbers (Alt+Enter toggles fullscreen)"),
+        )
+        .arg(
+            Arg::with_name("hud")
+                .long("hud")
+                .help("Shows instructions per second, FPS and speed on top of the display (F11 toggles it)"),
+        )
+        .arg(
+            Arg::with_name("keypad")
+                .long("keypad")
+                .help("Shows a hex keypad right of the display that can be clicked or touched"),
+        )
+        .arg(
+            Arg::with_name("deflicker")
+                .long("deflicker")
+                .help("Reduces flicker by ORing the last two frames or letting pixels fade out")
+                .possible_values(&["off", "or", "decay"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("phosphor-decay")
+                .long("phosphor-decay")
+                .help("Share of its brightness a pixel keeps per frame with --deflicker decay")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .help("CRT effects applied in software, comma-separated: scanlines, grid, bloom, curvature")
+                .takes_value(true)
+                .use_delimiter(true)
+                .possible_values(&chip8::FILTER_NAMES),
+        )
+        .arg(
+            Arg::with_name("record-video")
+                .long("record-video")
+                .help("Records every emulated frame as animated GIF (.gif) or as Y4M-video plus WAV-audio (.y4m)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("input-script")
+                .long("input-script")
+                .help("Presses and releases keys at given frames or cycles, e.g. \"frame 120: press 5; frame 130: release 5\"")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("frontend")
+                .long("frontend")
+                .help("Shows the emulator in an SDL window or in the terminal (without SDL)")
+                .possible_values(&["window", "terminal"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keymap")
+                .long("keymap")
+                .help("Keyboard layout for the CHIP-8 keys, physical uses the key positions regardless of the layout")
+                .possible_values(&chip8::KEYMAP_NAMES)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("terminal-keys")
+                .long("terminal-keys")
+                .help("The 16 keys used for the CHIP-8 keys 0 to F in the terminal frontend")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("key-release-timeout")
+                .long("key-release-timeout")
+                .help("Milliseconds without key repeat after which the terminal frontend releases a key")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("cfg")
+                .about("Extracts the control-flow graph of a ROM as Graphviz DOT")
+                .arg(
+                    Arg::with_name("rom")
+                        .help("Path to a valid CHIP-8 ROM")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .help("Path of the DOT-file to write (default: stdout)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("state")
+                .about("Inspects save states")
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Prints registers, stack, code at PC and display of a save state")
+                        .arg(
+                            Arg::with_name("state")
+                                .help("Path to a save state")
+                                .required(true)
+                                .index(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about("Prints the differences between two save states")
+                        .arg(
+                            Arg::with_name("a")
+                                .help("Path to the first save state")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("b")
+                                .help("Path to the second save state")
+                                .required(true)
+                                .index(2),
+                        ),
+                ),
+        )
         .get_matches();
 
+    match matches.subcommand() {
+        ("cfg", Some(matches)) => {
+            return Command::ControlFlowGraph {
+                path: matches.value_of("rom").unwrap().to_string(),
+                output: matches.value_of("output").map(str::to_string),
+            };
+        }
+        ("state", Some(matches)) => match matches.subcommand() {
+            ("show", Some(matches)) => {
+                return Command::ShowState {
+                    path: matches.value_of("state").unwrap().to_string(),
+                };
+            }
+            ("diff", Some(matches)) => {
+                return Command::DiffStates {
+                    a: matches.value_of("a").unwrap().to_string(),
+                    b: matches.value_of("b").unwrap().to_string(),
+                };
+            }
+            _ => {
+                eprintln!("ERROR: Expected 'state show' or 'state diff'. Exiting...");
+                std::process::exit(1);
+            }
+        },
+        _ => (),
+    }
+
+    let config_path = matches
+        .value_of("config")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(config::default_config_path);
+    let config = config::Config::read_from_file(&config_path).unwrap_or_else(|err| {
+        eprintln!(
+            "ERROR: Could not read config file {}: {}. Exiting...",
+            config_path.display(),
+            err
+        );
+        std::process::exit(1);
+    });
+
     let path = matches
         .value_of("path")
         .expect("ERROR: No ROM given as an argument. Exiting...")
@@ -39,56 +551,283 @@ fn parse_command_arguments() -> (String, u32) {
     let frequency = frequency
         .parse::<u32>()
         .expect("ERROR: Could not parse given frequency to integer. Exiting...");
-    (path, frequency)
+    let sprite_viewer = if matches.is_present("sprite-viewer") {
+        Some(parse_sprite_viewer_config(
+            matches
+                .value_of("sprite-range")
+                .unwrap_or(DEFAULT_SPRITE_RANGE),
+            matches
+                .value_of("sprite-height")
+                .unwrap_or(DEFAULT_SPRITE_HEIGHT),
+        ))
+    } else {
+        None
+    };
+    let rewind_seconds = matches
+        .value_of("rewind-seconds")
+        .unwrap_or(DEFAULT_REWIND_SECONDS)
+        .parse::<u32>()
+        .expect("ERROR: Could not parse given rewind-seconds to integer. Exiting...");
+    let rewind_granularity = matches
+        .value_of("rewind-granularity")
+        .unwrap_or(DEFAULT_REWIND_GRANULARITY)
+        .parse::<u32>()
+        .ok()
+        .filter(|granularity| *granularity > 0)
+        .expect("ERROR: Rewind-granularity must be a positive integer. Exiting...");
+    let scale = match matches.value_of("scale") {
+        Some(scale) => scale.parse::<u32>().ok(),
+        None => Some(config.scale.unwrap_or(chip8::DEFAULT_SCALE_FACTOR)),
+    }
+    .filter(|scale| *scale > 0)
+    .expect("ERROR: Scale must be a positive integer. Exiting...");
+    let phosphor_decay = matches
+        .value_of("phosphor-decay")
+        .unwrap_or(DEFAULT_PHOSPHOR_DECAY)
+        .parse::<f32>()
+        .ok()
+        .filter(|decay| (0.0..1.0).contains(decay))
+        .expect("ERROR: Phosphor-decay must be between 0.0 and 1.0 (exclusive). Exiting...");
+    let persistence = match matches.value_of("deflicker") {
+        Some("or") => chip8::Persistence::LastTwoFrames,
+        Some("decay") => chip8::Persistence::Decay(phosphor_decay),
+        _ => chip8::Persistence::Off,
+    };
+    let filters = matches
+        .values_of("filter")
+        .map(|names| names.filter_map(chip8::CrtFilter::from_name).collect())
+        .unwrap_or_default();
+    let frontend = match matches
+        .value_of("frontend")
+        .or(config.frontend.as_deref())
+        .unwrap_or("window")
+    {
+        "window" => chip8::FrontendConfig::Window,
+        "terminal" => chip8::FrontendConfig::Terminal(parse_terminal_config(&matches, &config)),
+        frontend => {
+            eprintln!(
+                "ERROR: Unknown frontend {} (expected window or terminal). Exiting...",
+                frontend
+            );
+            std::process::exit(1);
+        }
+    };
+    let code_writes = match matches.value_of("code-writes") {
+        Some("warn") => Some(chip8::CodeWriteAction::Warn),
+        Some("break") => Some(chip8::CodeWriteAction::Break),
+        _ => None,
+    };
+
+    let controller_mapping = build_controller_mapping(&config, &path);
+
+    Command::Run(Box::new(Arguments {
+        path,
+        frequency,
+        sprite_viewer,
+        code_writes,
+        strict: matches.is_present("strict"),
+        load_state: matches.value_of("load-state").map(str::to_string),
+        rewind_seconds,
+        rewind_granularity,
+        record: matches.value_of("record").map(str::to_string),
+        play: matches.value_of("play").map(str::to_string),
+        resume: !matches.is_present("no-resume") && config.resume.unwrap_or(true),
+        clear_resume: matches.is_present("clear-resume"),
+        palette: build_palette(&matches, &config),
+        scale,
+        integer_scaling: matches.is_present("integer-scaling")
+            || config.integer_scaling.unwrap_or(false),
+        hud: matches.is_present("hud") || config.hud.unwrap_or(false),
+        keypad: matches.is_present("keypad") || config.keypad.unwrap_or(false),
+        persistence,
+        filters,
+        record_video: matches.value_of("record-video").map(str::to_string),
+        input_script: matches.value_of("input-script").map(str::to_string),
+        frontend,
+        keymap: build_keymap(&matches, &config),
+        controller_mapping,
+    }))
 }
 
-fn main() {
-    let (path, frequency) = parse_command_arguments();
+fn ask_to_resume() -> bool {
+    print!("Found a suspended session of this ROM. Resume it? [Y/n] ");
+    std::io::stdout().flush().ok();
 
-    let mut chip8 = chip8::Chip8::init();
+    // without a terminal (e.g. when started from a launcher) stdin is at its end at once,
+    // which is taken as no
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => {
+            println!();
+            false
+        }
+        Ok(_) => !answer.trim().to_lowercase().starts_with('n'),
+    }
+}
 
-    chip8.load_program(&path);
-    chip8.start_timers();
-    chip8.start_sound_system();
+fn extract_control_flow_graph(path: &str, output: Option<&str>) {
+    let rom = std::fs::read(path).expect("ERROR: Could not load chip8 program. Exiting...");
+    let graph = chip8::ControlFlowGraph::extract(&rom);
 
-    let mut display_time = Instant::now();
+    let mut report = String::new();
+    for address in graph.indirect_jumps() {
+        report += &format!(
+            "Indirect jump (BNNN) at {:#05x} can not be followed\n",
+            address
+        );
+    }
+    for (start, end) in graph.data_regions() {
+        report += &format!(
+            "Unreachable, assumed data: {:#05x}-{:#05x}\n",
+            start,
+            end - 1
+        );
+    }
 
-    let mut count_display_time = Instant::now();
-    let mut cycle_count = 0;
-    let mut draw_count = 0;
+    match output {
+        Some(output) => {
+            std::fs::write(output, graph.to_dot())
+                .expect("ERROR: Could not write DOT-file. Exiting...");
+            print!("{}", report);
+        }
+        None => {
+            print!("{}", graph.to_dot());
+            eprint!("{}", report);
+        }
+    }
+}
 
-    while !chip8.should_exit() {
-        // emulate cycle
-        let time_before = Instant::now();
-        chip8.process_events();
-        chip8.emulate_cycle();
-        cycle_count += 1;
-        let cycle_time = Instant::now().duration_since(time_before);
+fn read_state(path: &str) -> chip8::MachineState {
+    chip8::MachineState::read_from_file(std::path::Path::new(path)).unwrap_or_else(|err| {
+        eprintln!("ERROR: Could not read state {}: {}. Exiting...", path, err);
+        std::process::exit(1);
+    })
+}
 
-        // at 60Hz, update the screen
-        let crnt_time = Instant::now();
-        if crnt_time - display_time >= Duration::new(0, 16666667) {
-            chip8.draw_display();
-            display_time = crnt_time;
-            draw_count += 1;
+fn main() {
+    let arguments = match parse_command_arguments() {
+        Command::Run(arguments) => *arguments,
+        Command::ControlFlowGraph { path, output } => {
+            extract_control_flow_graph(&path, output.as_deref());
+            return;
+        }
+        Command::ShowState { path } => {
+            print!("{}", chip8::show_state(&read_state(&path)));
+            return;
+        }
+        Command::DiffStates { a, b } => {
+            print!("{}", chip8::diff_states(&read_state(&a), &read_state(&b)));
+            return;
         }
+    };
 
-        if crnt_time - count_display_time >= Duration::new(1, 0) {
-            println!("Cycles in this second: {}", cycle_count);
-            println!("Draws in this second: {}", draw_count);
-            cycle_count = 0;
-            draw_count = 0;
-            count_display_time = crnt_time;
+    let mut chip8 = chip8::Chip8::init(arguments.frontend);
+
+    chip8.set_palette(arguments.palette);
+    // the keypad panel changes the size of the window
+    chip8.show_keypad_panel(arguments.keypad);
+    chip8.set_window_scale(arguments.scale, arguments.integer_scaling);
+    chip8.set_persistence(arguments.persistence);
+    chip8.set_filters(arguments.filters);
+    chip8.set_keymap(arguments.keymap);
+    chip8.set_controller_mapping(arguments.controller_mapping);
+    chip8.show_statistics(arguments.hud);
+    chip8.load_program(&arguments.path);
+    if arguments.clear_resume {
+        chip8.clear_resume_state();
+    }
+    // a fresh start is needed for save states and movies. sessions started from a save
+    // state are still suspended on exit, movies never are (see suspend_session)
+    let offer_resume = arguments.resume
+        && arguments.load_state.is_none()
+        && arguments.record.is_none()
+        && arguments.play.is_none();
+    if offer_resume && chip8.has_resume_state() && ask_to_resume() {
+        if let Err(err) = chip8.resume_session() {
+            eprintln!("ERROR: Could not resume session: {}", err);
+        }
+    }
+    if let Some(path) = &arguments.load_state {
+        if let Err(err) = chip8.load_state_from_file(std::path::Path::new(path)) {
+            eprintln!("ERROR: Could not load state {}: {}. Exiting...", path, err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(config) = arguments.sprite_viewer {
+        chip8.open_sprite_viewer(config);
+    }
+    if let Some(action) = arguments.code_writes {
+        chip8.monitor_code_writes(action);
+    }
+    if arguments.strict {
+        chip8.enable_strict_mode();
+    }
+    let mut frequency = arguments.frequency;
+    if let Some(path) = &arguments.record {
+        chip8.record_movie(std::path::Path::new(path), frequency);
+    }
+    if let Some(path) = &arguments.play {
+        frequency = chip8
+            .play_movie(std::path::Path::new(path))
+            .unwrap_or_else(|err| {
+                eprintln!("ERROR: Could not play movie {}: {}. Exiting...", path, err);
+                std::process::exit(1);
+            });
+    }
+    if let Some(path) = &arguments.record_video {
+        if let Err(err) = chip8.record_video(std::path::Path::new(path), arguments.scale) {
+            eprintln!(
+                "ERROR: Could not record video to {}: {}. Exiting...",
+                path, err
+            );
+            std::process::exit(1);
         }
+    }
+    if let Some(path) = &arguments.input_script {
+        let inputs = chip8::read_input_script(std::path::Path::new(path)).unwrap_or_else(|err| {
+            eprintln!(
+                "ERROR: Could not read input script {}: {}. Exiting...",
+                path, err
+            );
+            std::process::exit(1);
+        });
+        for input in inputs {
+            chip8
+                .schedule_input(input)
+                .expect("ERROR: Invalid key in the input script. Exiting...");
+        }
+    }
+    if arguments.rewind_seconds > 0 {
+        chip8.enable_rewind(
+            (arguments.rewind_seconds as u64 * FRAMES_PER_SECOND) as usize,
+            arguments.rewind_granularity,
+        );
+    }
+    chip8.start_sound_system();
+
+    let mut next_frame_time = Instant::now();
 
-        // sleep for rest of the duration until next cycle
-        let sleep_per_cycle = Duration::new(0, 1_000_000_000 / frequency);
-        sleep_per_cycle.checked_sub(cycle_time).take().map(sleep);
+    while !chip8.should_exit() {
+        chip8.process_events();
+        chip8.emulate_frame(frequency);
+        chip8.draw_display();
+
+        // sleep for the rest of the frame. if the emulation fell behind, don't try to catch up
+        next_frame_time += FRAME_DURATION;
+        match next_frame_time.checked_duration_since(Instant::now()) {
+            Some(duration) => sleep(duration),
+            None => next_frame_time = Instant::now(),
+        }
     }
 
     // TODO: dont forget to implement drop for chip8: you must de-init everything
-    // TODO: (do it recursively for display-sdl2 etc.) and join the threads!
-    println!("Stopping CHIP-8's timer-thread...");
+    // TODO: (do it recursively for display-sdl2 etc.)
     chip8.stop_sound_system();
-    chip8.stop_timers();
+    if arguments.resume {
+        chip8.suspend_session();
+    }
+    chip8.finish_movie();
+    chip8.finish_video();
+    chip8.save_rpl_flags();
+    chip8.print_strict_summary();
 }